@@ -1,22 +1,214 @@
-use itertools::izip;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
 
 fn main() {
     const INPUT: &str = include_str!("../inputs/1.txt");
-    let nums = INPUT.lines().map(|x| x.parse::<i32>().unwrap());
-    println!("Number of increases: {}", count_larger(nums.clone()));
 
-    let triple_sums = izip!(nums.clone(), nums.clone().skip(1), nums.skip(2)).map(|(a,b,c)| a+b+c);
-    println!("Number of increases on triples: {}", count_larger(triple_sums));
+    // Optionally a different (possibly huge) file of depths to sweep through
+    let read = |size: usize| -> io::Result<SweepReport> {
+        match std::env::args().nth(1) {
+            Some(path) => analyse(BufReader::new(File::open(path)?), size),
+            None => analyse(INPUT.as_bytes(), size),
+        }
+    };
+
+    let single = read(1).unwrap();
+    println!("Number of increases: {}", single.increases);
+
+    let triples = read(3).unwrap();
+    println!("Number of increases on triples: {}", triples.increases);
+    for comparison in [Comparison::Decrease, Comparison::Plateau] {
+        println!(
+            "{:?}s: {} single, {} on triples",
+            comparison,
+            single.count(comparison),
+            triples.count(comparison)
+        );
+    }
+    println!(
+        "Longest increasing run: {} depths, {} triples",
+        single.longest_increasing_run, triples.longest_increasing_run
+    );
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Comparison {
+    Increase,
+    Decrease,
+    Plateau,
 }
 
-fn count_larger(ls: impl Iterator<Item = i32>) -> i32 {
-    let mut count = 0;
-    let mut last = 100000000;
-    for num in ls {
-        if last < num {
-            count += 1;
+impl Comparison {
+    fn of(previous: i64, next: i64) -> Comparison {
+        match next.cmp(&previous) {
+            std::cmp::Ordering::Greater => Comparison::Increase,
+            std::cmp::Ordering::Less => Comparison::Decrease,
+            std::cmp::Ordering::Equal => Comparison::Plateau,
         }
-        last = num;
     }
-    count
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct SweepReport {
+    // How many full windows there were
+    windows: usize,
+    increases: usize,
+    decreases: usize,
+    plateaus: usize,
+    // In windows, so a single window is a run of 1
+    longest_increasing_run: usize,
+}
+
+impl SweepReport {
+    fn count(&self, comparison: Comparison) -> usize {
+        match comparison {
+            Comparison::Increase => self.increases,
+            Comparison::Decrease => self.decreases,
+            Comparison::Plateau => self.plateaus,
+        }
+    }
+}
+
+// Compares the sums of consecutive windows of depths, one depth at a time.
+// Only the current window is kept around, so memory use doesn't depend on
+// how many depths there are.
+struct WindowAnalyser {
+    size: usize,
+    window: VecDeque<i64>,
+    sum: i64,
+    previous: Option<i64>,
+    run: usize,
+    report: SweepReport,
+}
+
+impl WindowAnalyser {
+    fn new(size: usize) -> WindowAnalyser {
+        assert!(0 < size, "windows need at least one depth");
+        WindowAnalyser {
+            size,
+            window: VecDeque::with_capacity(size + 1),
+            sum: 0,
+            previous: None,
+            run: 0,
+            report: SweepReport::default(),
+        }
+    }
+
+    fn push(&mut self, depth: i64) {
+        self.window.push_back(depth);
+        self.sum += depth;
+        if self.size < self.window.len() {
+            self.sum -= self.window.pop_front().unwrap();
+        }
+        if self.window.len() < self.size {
+            return;
+        }
+
+        let report = &mut self.report;
+        report.windows += 1;
+        self.run = match self.previous.map(|p| Comparison::of(p, self.sum)) {
+            Some(Comparison::Increase) => {
+                report.increases += 1;
+                self.run + 1
+            }
+            Some(Comparison::Decrease) => {
+                report.decreases += 1;
+                1
+            }
+            Some(Comparison::Plateau) => {
+                report.plateaus += 1;
+                1
+            }
+            None => 1,
+        };
+        report.longest_increasing_run = report.longest_increasing_run.max(self.run);
+        self.previous = Some(self.sum);
+    }
+}
+
+// One depth per line. Blank lines are skipped, anything else that isn't a
+// number is an error.
+fn analyse(reader: impl BufRead, size: usize) -> io::Result<SweepReport> {
+    let mut analyser = WindowAnalyser::new(size);
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let depth = line.parse().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Line {}: '{}' is not a depth", i + 1, line),
+            )
+        })?;
+        analyser.push(depth);
+    }
+    Ok(analyser.report)
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = "199\n200\n208\n210\n200\n207\n240\n269\n260\n263";
+
+#[test]
+fn test_example() {
+    let single = analyse(EXAMPLE.as_bytes(), 1).unwrap();
+    assert_eq!(
+        single,
+        SweepReport {
+            windows: 10,
+            increases: 7,
+            decreases: 2,
+            plateaus: 0,
+            longest_increasing_run: 4
+        }
+    );
+    let triples = analyse(EXAMPLE.as_bytes(), 3).unwrap();
+    assert_eq!(triples.count(Comparison::Increase), 5);
+    assert_eq!(triples.count(Comparison::Plateau), 1);
+    assert_eq!(triples.count(Comparison::Decrease), 1);
+    assert_eq!(triples.windows, 8);
+}
+
+#[test]
+fn test_window_sizes() {
+    // Larger windows than there are depths never fill up
+    let report = analyse(EXAMPLE.as_bytes(), 11).unwrap();
+    assert_eq!(report, SweepReport::default());
+
+    // A window of everything is a single sum
+    let report = analyse(EXAMPLE.as_bytes(), 10).unwrap();
+    assert_eq!(report.windows, 1);
+    assert_eq!(report.longest_increasing_run, 1);
+}
+
+#[test]
+fn test_streaming() {
+    // Counts up to a million then back down, never stored as a whole
+    struct Depths(i64);
+    impl io::Read for Depths {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if 2_000_000 <= self.0 {
+                return Ok(0);
+            }
+            let depth = 1_000_000 - (1_000_000 - self.0).abs();
+            self.0 += 1;
+            let line = format!("{}\n", depth);
+            buf[..line.len()].copy_from_slice(line.as_bytes());
+            Ok(line.len())
+        }
+    }
+    let report = analyse(BufReader::new(Depths(0)), 2).unwrap();
+    assert_eq!(report.windows, 1_999_999);
+    assert_eq!(report.longest_increasing_run, 1_000_000);
+    // The two windows on either side of the peak
+    assert_eq!(report.plateaus, 1);
+}
+
+#[test]
+fn test_bad_depth() {
+    let error = analyse("1\n2\n\nthree\n".as_bytes(), 1).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(error.to_string(), "Line 4: 'three' is not a depth");
+}
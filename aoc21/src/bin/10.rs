@@ -1,7 +1,122 @@
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq)]
 enum SyntaxStatus {
     Perfect,
-    Error(char),
-    Incomplete(Vec<char>),
+    // Column of the first illegal closer, which closer was expected there
+    // (none if nothing was open), and the one that was found
+    Error {
+        column: usize,
+        expected: Option<char>,
+        found: char,
+    },
+    // The closers needed to complete the line
+    Incomplete(String),
+}
+
+struct Checker {
+    // Opening and closing delimiter pairs
+    delimiters: Vec<(char, char)>,
+    error_scores: HashMap<char, u64>,
+    completion_scores: HashMap<char, u64>,
+}
+
+impl Checker {
+    fn standard() -> Checker {
+        Checker {
+            delimiters: vec![('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')],
+            error_scores: HashMap::from([(')', 3), (']', 57), ('}', 1197), ('>', 25137)]),
+            completion_scores: HashMap::from([(')', 1), (']', 2), ('}', 3), ('>', 4)]),
+        }
+    }
+
+    fn get_matching(&self, c: char) -> Option<char> {
+        self.delimiters
+            .iter()
+            .find(|(open, _)| *open == c)
+            .map(|(_, close)| *close)
+    }
+
+    fn is_closer(&self, c: char) -> bool {
+        self.delimiters.iter().any(|(_, close)| *close == c)
+    }
+
+    fn syntax_check(&self, line: &str) -> SyntaxStatus {
+        // The closers of all the currently open chunks
+        let mut stack: Vec<char> = Vec::new();
+        for (column, c) in line.chars().enumerate() {
+            if let Some(close) = self.get_matching(c) {
+                stack.push(close);
+            } else if self.is_closer(c) {
+                match stack.pop() {
+                    Some(expected) if expected == c => (),
+                    expected => {
+                        return SyntaxStatus::Error {
+                            column,
+                            expected,
+                            found: c,
+                        }
+                    }
+                }
+            }
+        }
+
+        if stack.is_empty() {
+            SyntaxStatus::Perfect
+        } else {
+            SyntaxStatus::Incomplete(stack.into_iter().rev().collect())
+        }
+    }
+
+    fn error_score(&self, status: &SyntaxStatus) -> u64 {
+        match status {
+            SyntaxStatus::Error { found, .. } => *self.error_scores.get(found).unwrap_or(&0),
+            _ => 0,
+        }
+    }
+
+    fn completion_score(&self, status: &SyntaxStatus) -> Option<u64> {
+        match status {
+            SyntaxStatus::Incomplete(rest) => Some(rest.chars().fold(0, |l, r| {
+                l * 5 + self.completion_scores.get(&r).unwrap_or(&0)
+            })),
+            _ => None,
+        }
+    }
+}
+
+// The line with a caret pointing out what's wrong with it
+fn explain(line: &str, status: &SyntaxStatus) -> Option<String> {
+    let (column, message) = match status {
+        SyntaxStatus::Perfect => return None,
+        SyntaxStatus::Error {
+            column,
+            expected: Some(expected),
+            found,
+        } => (
+            *column,
+            format!("Expected {}, but found {} instead.", expected, found),
+        ),
+        SyntaxStatus::Error {
+            column,
+            expected: None,
+            found,
+        } => (*column, format!("Found {} with nothing to close.", found)),
+        SyntaxStatus::Incomplete(rest) => (
+            line.chars().count(),
+            format!("Incomplete, complete by adding {}.", rest),
+        ),
+    };
+    Some(format!("{}\n{}^ {}", line, " ".repeat(column), message))
+}
+
+fn middle_completion_score(checker: &Checker, statuses: &[SyntaxStatus]) -> Option<u64> {
+    let mut scores: Vec<u64> = statuses
+        .iter()
+        .filter_map(|status| checker.completion_score(status))
+        .collect();
+    scores.sort();
+    scores.get(scores.len() / 2).copied()
 }
 
 fn main() {
@@ -16,84 +131,107 @@ fn main() {
     //             <{([([[(<>()){}]>(<<{{
     //             <{([{{}}[<[[[<>{}]]]>[]]";
     const INPUT: &str = include_str!("../inputs/10.txt");
+    let checker = Checker::standard();
 
     let lines: Vec<&str> = INPUT.lines().map(|s| s.trim()).collect();
-    let syntax_results: Vec<SyntaxStatus> = lines.into_iter().map(syntax_check).collect();
-    let corrupt_score = syntax_results
+    let syntax_results: Vec<SyntaxStatus> = lines
         .iter()
-        .map(|status| {
-            if let SyntaxStatus::Error(illegal) = status {
-                return match illegal {
-                    ')' => 3,
-                    ']' => 57,
-                    '}' => 1197,
-                    '>' => 25137,
-                    _ => 0,
-                };
+        .map(|line| checker.syntax_check(line))
+        .collect();
+
+    if std::env::args().any(|a| a == "--explain") {
+        for (line, status) in lines.iter().zip(&syntax_results) {
+            if let Some(explanation) = explain(line, status) {
+                println!("{}\n", explanation);
             }
-            0
-        })
-        .sum::<u32>();
-    println!("Total syntax error score: {}", corrupt_score);
+        }
+    }
 
-    let mut incomplete_scores: Vec<u64> = syntax_results
+    let corrupt_score = syntax_results
         .iter()
-        .filter_map(|status| {
-            if let SyntaxStatus::Incomplete(rest) = status {
-                Some(rest)
-            } else {
-                None
-            }
-        })
-        .map(|xs| {
-            xs.into_iter()
-                .map(|x| {
-                    return match x {
-                        ')' => 1,
-                        ']' => 2,
-                        '}' => 3,
-                        '>' => 4,
-                        _ => 0,
-                    };
-                })
-                .fold(0, |l, r| l * 5 + r)
-        })
-        .collect();
-    incomplete_scores.sort();
+        .map(|status| checker.error_score(status))
+        .sum::<u64>();
+    println!("Total syntax error score: {}", corrupt_score);
 
-    println!("Middle incomplete score is {}", incomplete_scores.get(incomplete_scores.len() / 2).unwrap());
+    println!(
+        "Middle incomplete score is {}",
+        middle_completion_score(&checker, &syntax_results).unwrap()
+    );
 }
 
-fn get_matching(c: char) -> char {
-    match c {
-        '(' => ')',
-        '[' => ']',
-        '{' => '}',
-        '<' => '>',
-        _ => c
-    }
-}
+#[cfg(test)]
+const EXAMPLE: &str = "[({(<(())[]>[[{[]{<()<>>
+[(()[<>])]({[<{<<[]>>(
+{([(<{}[<>[]}>{[]{[(<()>
+(((({<>}<{<{<>}{[]{[]{}
+[[<[([]))<([[{}[[()]]]
+[{[{({}]{}}([{[{{{}}([]
+{<[[]]>}<{[{[{[]{()[[[]
+[<(<(<(<{}))><([]([]()
+<{([([[(<>()){}]>(<<{{
+<{([{{}}[<[[[<>{}]]]>[]]";
 
-fn syntax_check(line: &str) -> SyntaxStatus {
-    let mut stack: Vec<char> = Vec::new();
-    for c in line.chars() {
-        match c {
-            '(' | '[' | '{' | '<' => stack.push(c),
-            ')' | ']' | '}' | '>' => {
-                if let Some(top) = stack.last() {
-                    if c != get_matching(*top) {
-                        return SyntaxStatus::Error(c);
-                    }
-                    stack.pop();
-                }
-            }
-            _ => (),
+#[test]
+fn test_example() {
+    let checker = Checker::standard();
+    let statuses: Vec<SyntaxStatus> = EXAMPLE.lines().map(|l| checker.syntax_check(l)).collect();
+    assert_eq!(
+        statuses.iter().map(|s| checker.error_score(s)).sum::<u64>(),
+        26397
+    );
+    assert_eq!(middle_completion_score(&checker, &statuses), Some(288957));
+    assert_eq!(
+        statuses[0],
+        SyntaxStatus::Incomplete("}}]])})]".to_string())
+    );
+    assert_eq!(
+        statuses[2],
+        SyntaxStatus::Error {
+            column: 12,
+            expected: Some(']'),
+            found: '}'
         }
-    }
+    );
+}
 
-    if stack.is_empty() {
-        SyntaxStatus::Perfect
-    } else {
-        SyntaxStatus::Incomplete(stack.into_iter().rev().map(get_matching).collect())
-    }
+#[test]
+fn test_explain() {
+    let checker = Checker::standard();
+    let line = "{([(<{}[<>[]}>{[]{[(<()>";
+    assert_eq!(
+        explain(line, &checker.syntax_check(line)).unwrap(),
+        "{([(<{}[<>[]}>{[]{[(<()>\n            ^ Expected ], but found } instead."
+    );
+    let line = "[({(<(())";
+    assert_eq!(
+        explain(line, &checker.syntax_check(line)).unwrap(),
+        "[({(<(())\n         ^ Incomplete, complete by adding >)})]."
+    );
+    let line = "()]";
+    assert_eq!(
+        explain(line, &checker.syntax_check(line)).unwrap(),
+        "()]\n  ^ Found ] with nothing to close."
+    );
+    assert_eq!(explain("<>", &checker.syntax_check("<>")), None);
+}
+
+#[test]
+fn test_custom_delimiters() {
+    let checker = Checker {
+        delimiters: vec![('«', '»'), ('(', ')')],
+        error_scores: HashMap::from([('»', 10), (')', 1)]),
+        completion_scores: HashMap::from([('»', 2), (')', 1)]),
+    };
+    // Not delimiters in this table, so just regular characters
+    assert_eq!(
+        checker.syntax_check("«a[b)»"),
+        SyntaxStatus::Error {
+            column: 4,
+            expected: Some('»'),
+            found: ')'
+        }
+    );
+    let status = checker.syntax_check("«(«[»");
+    assert_eq!(status, SyntaxStatus::Incomplete(")»".to_string()));
+    assert_eq!(checker.completion_score(&status), Some(5 + 2));
 }
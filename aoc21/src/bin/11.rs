@@ -1,96 +1,102 @@
-use std::collections::VecDeque;
+use common::automaton::{Automaton, Grid, Rule};
+use common::Pos;
 
-enum SimulationResult {
-    FlashCount(u32),
-    Synchronized(u32)
-}
+// Energy levels rise by one every step, and an octopus above 9 flashes,
+// raising the energy of everything around it before going back to 0.
+struct Octopuses;
 
-fn main() {
-//     const INPUT: &str = "5483143223
-// 2745854711
-// 5264556173
-// 6141336146
-// 6357385478
-// 4167524645
-// 2176841721
-// 6882881134
-// 4846848554
-// 5283751526";
-    const INPUT: &str = include_str!("../inputs/11.txt");
+impl Rule for Octopuses {
+    type Cell = u8;
+
+    fn update(&self, _phase: usize, grid: &Grid<u8>, pos: Pos) -> u8 {
+        grid.get(pos) + 1
+    }
 
-    let octopuses: Vec<Vec<u32>> = INPUT.lines().map(|l|l.chars().map(|c|c.to_digit(10).unwrap()).collect()).collect();
-    for simulation_steps in [100, 1000] {
-        let results = simulate(octopuses.clone(), simulation_steps);
-        match results {
-            SimulationResult::FlashCount(flash_count) => println!("Flash count: {}", flash_count),
-            SimulationResult::Synchronized(step) => println!("Syncronized on step {}", step)
-        }
+    fn fires(&self, energy: &u8) -> bool {
+        9 < *energy
+    }
+
+    fn spread(&self, energy: &mut u8) {
+        *energy = energy.saturating_add(1);
+    }
+
+    fn settle(&self, energy: &mut u8) {
+        *energy = 0;
     }
 }
 
-fn simulate(mut octopi: Vec<Vec<u32>>, n: u32) -> SimulationResult {
-    let mut flash_count = 0;
-    for m in 0..n {
-        let mut flash_stack: VecDeque<(usize, usize)> = VecDeque::new();
-        let mut done_flash: Vec<(usize, usize)> = Vec::new();
+fn parse_input(input: &str) -> Grid<u8> {
+    Grid::parse(input, |c| c.to_digit(10).unwrap_or(0) as u8)
+}
 
-        for (j, xs) in octopi.iter_mut().enumerate() {
-            for (i, octopus) in xs.iter_mut().enumerate() {
-                *octopus += 1;
-                if 9 < *octopus {
-                    flash_stack.push_front((i, j));
-                }
-            }
-        }
+fn flash_count(grid: &Grid<u8>, steps: usize) -> usize {
+    let mut octopuses = Automaton::new(Octopuses, grid.clone());
+    octopuses.run(steps).iter().map(|s| s.fired).sum()
+}
 
-        while let Some(coord) = flash_stack.pop_back() {
-            let surrounding = get_surrounding_coords(coord);
-            for neighbour in surrounding {
-                if let Some((i, j)) = neighbour {
-                    if let Some(xs) = octopi.get_mut(j) {
-                        if let Some(octopus) = xs.get_mut(i) {
-                            *octopus += 1;
-                            if 10 == *octopus {
-                                flash_stack.push_front((i, j));
-                            }
-                        }
-                    }
-                }
-            }
-            done_flash.push(coord);
-            flash_count += 1;
-        }
+// Every octopus flashing in the same step
+fn first_synchronised_step(grid: &Grid<u8>, limit: usize) -> Option<usize> {
+    let all = grid.cells.len();
+    Automaton::new(Octopuses, grid.clone())
+        .run_until(limit, |stats| stats.fired == all)
+        .map(|stats| stats.step)
+}
 
-        if done_flash.len() == octopi.iter().map(|ls|ls.len()).sum() {
-            return SimulationResult::Synchronized(m + 1);
-        }
+fn main() {
+    //     const INPUT: &str = "5483143223
+    // 2745854711
+    // 5264556173
+    // 6141336146
+    // 6357385478
+    // 4167524645
+    // 2176841721
+    // 6882881134
+    // 4846848554
+    // 5283751526";
+    const INPUT: &str = include_str!("../inputs/11.txt");
+    let octopuses = parse_input(INPUT);
 
-        for (i, j) in done_flash {
-            if let Some(xs) = octopi.get_mut(j) {
-                if let Some(octopus) = xs.get_mut(i) {
-                    *octopus = 0;
-                }
-            }
-        }
+    println!("Flash count: {}", flash_count(&octopuses, 100));
+    match first_synchronised_step(&octopuses, 1000) {
+        Some(step) => println!("Syncronized on step {}", step),
+        None => println!("Not syncronized within 1000 steps"),
     }
-    SimulationResult::FlashCount(flash_count)
 }
 
-fn get_surrounding_coords((i, j): (usize, usize)) -> [Option<(usize, usize)>; 8] {
-    let mut k = 0;
-    let mut out = [None; 8];
-    for y in -1..2 {
-        for x in -1..2 {
-            if x == 0 && y == 0 {
-                continue;
-            }
-            if let (Ok(a), Ok(b)) = (i32::try_from(i), i32::try_from(j)) {
-                if let (Ok(a), Ok(b)) = (usize::try_from(a + x), usize::try_from(b + y)) {
-                    out[k] = Some((a, b));
-                    k += 1;
-                }
-            }
-        }
-    }
-    out
-}
\ No newline at end of file
+#[cfg(test)]
+const EXAMPLE: &str = "5483143223
+2745854711
+5264556173
+6141336146
+6357385478
+4167524645
+2176841721
+6882881134
+4846848554
+5283751526";
+
+#[test]
+fn test_small_example() {
+    let grid = parse_input("11111\n19991\n19191\n19991\n11111");
+    let mut octopuses = Automaton::new(Octopuses, grid);
+    let stats = octopuses.step();
+    assert_eq!(stats.fired, 9);
+    assert_eq!(
+        octopuses.grid.render(|e| (b'0' + e) as char),
+        "34543\n40004\n50005\n40004\n34543"
+    );
+    octopuses.step();
+    assert_eq!(
+        octopuses.grid.render(|e| (b'0' + e) as char),
+        "45654\n51115\n61116\n51115\n45654"
+    );
+}
+
+#[test]
+fn test_example() {
+    let grid = parse_input(EXAMPLE);
+    assert_eq!(flash_count(&grid, 10), 204);
+    assert_eq!(flash_count(&grid, 100), 1656);
+    assert_eq!(first_synchronised_step(&grid, 1000), Some(195));
+    assert_eq!(first_synchronised_step(&grid, 100), None);
+}
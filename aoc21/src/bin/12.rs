@@ -1,8 +1,8 @@
-use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
-use std::rc::{Rc, Weak};
+use std::collections::HashMap;
+#[cfg(test)]
+use std::collections::HashSet;
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy, Debug)]
 enum CaveType {
     Start,
     End,
@@ -10,153 +10,228 @@ enum CaveType {
     Small,
 }
 
-struct Cave<'a> {
-    name: &'a str,
-    cave_type: CaveType,
-    neighbours: Vec<Weak<RefCell<Cave<'a>>>>,
+fn find_type(name: &str) -> CaveType {
+    match name {
+        "start" => CaveType::Start,
+        "end" => CaveType::End,
+        a if a.chars().next().is_some_and(|c| c.is_uppercase()) => CaveType::Big,
+        _ => CaveType::Small,
+    }
 }
 
-trait Graph {
-    fn traverse_all(&self, prev: &Vec<&str>, visit_small_caves_twice: bool) -> Vec<String>;
+// Caves are numbered in the order they first appear. Only small caves can't
+// be visited again, so only they get a bit in the mask of visited caves.
+struct CaveGraph<'a> {
+    names: Vec<&'a str>,
+    types: Vec<CaveType>,
+    // The cave's bit, or 0 for caves that aren't small
+    masks: Vec<u64>,
+    neighbours: Vec<Vec<usize>>,
 }
 
-impl Graph for Cave<'_> {
-    fn traverse_all(&self, prev: &Vec<&str>, visit_small_caves_twice: bool) -> Vec<String> {
-        let current_path = [&prev[..], &[self.name][..]].concat();
-        if CaveType::End == self.cave_type {
-            return vec![current_path.join(",")];
-        }
-        let mut mutators = Vec::new();
-        let firsts: Vec<String> = self
-            .neighbours
-            .iter()
-            .filter_map(|next| {
-                let next_cell = next.upgrade().unwrap();
-                let next_cave = next_cell.borrow();
-                if match next_cave.cave_type {
-                    CaveType::Start => false,
-                    CaveType::Small => {
-                        if prev.contains(&next_cave.name) {
-                            mutators.push(Rc::downgrade(&next_cell));
-                            false
-                        } else {
-                            true
-                        }
+impl<'a> CaveGraph<'a> {
+    fn new(input: &'a str) -> Result<CaveGraph<'a>, String> {
+        let mut graph = CaveGraph {
+            names: Vec::new(),
+            types: Vec::new(),
+            masks: Vec::new(),
+            neighbours: Vec::new(),
+        };
+        let mut ids: HashMap<&str, usize> = HashMap::new();
+        let mut small_caves = 0;
+        for edge in input.lines() {
+            if let Some((a_key, b_key)) = edge.split_once('-') {
+                let mut cave = |key| -> Result<usize, String> {
+                    if let Some(&id) = ids.get(key) {
+                        return Ok(id);
                     }
-                    _ => true,
-                } {
-                    Some(next_cave.traverse_all(&current_path, visit_small_caves_twice))
-                } else {
-                    None
+                    let cave_type = find_type(key);
+                    let mask = if cave_type == CaveType::Small {
+                        let bit = 1u64
+                            .checked_shl(small_caves)
+                            .ok_or("Too many small caves for the visited mask")?;
+                        small_caves += 1;
+                        bit
+                    } else {
+                        0
+                    };
+                    graph.names.push(key);
+                    graph.types.push(cave_type);
+                    graph.masks.push(mask);
+                    graph.neighbours.push(Vec::new());
+                    ids.insert(key, graph.names.len() - 1);
+                    Ok(graph.names.len() - 1)
+                };
+                let (a, b) = (cave(a_key)?, cave(b_key)?);
+                graph.neighbours[a].push(b);
+                graph.neighbours[b].push(a);
+            }
+        }
+        Ok(graph)
+    }
+
+    fn find(&self, cave_type: CaveType) -> Option<usize> {
+        self.types.iter().position(|t| *t == cave_type)
+    }
+
+    fn count_paths(&self, visit_small_cave_twice: bool) -> u64 {
+        match self.find(CaveType::Start) {
+            Some(start) => self.count_from(start, 0, !visit_small_cave_twice, &mut HashMap::new()),
+            None => 0,
+        }
+    }
+
+    // Paths to the end from a cave only depend on which small caves have been
+    // visited and whether the double visit has been used, not on the order.
+    fn count_from(
+        &self,
+        cave: usize,
+        visited: u64,
+        used_double_visit: bool,
+        memo: &mut HashMap<(usize, u64, bool), u64>,
+    ) -> u64 {
+        if self.types[cave] == CaveType::End {
+            return 1;
+        }
+        if let Some(count) = memo.get(&(cave, visited, used_double_visit)) {
+            return *count;
+        }
+
+        let mut count = 0;
+        for &next in &self.neighbours[cave] {
+            count += match self.types[next] {
+                CaveType::Start => 0,
+                CaveType::Big => self.count_from(next, visited, used_double_visit, memo),
+                _ if visited & self.masks[next] == 0 => {
+                    self.count_from(next, visited | self.masks[next], used_double_visit, memo)
                 }
-            })
-            .flatten()
-            .collect();
-
-        // If we haven't already visited a small cave twice,
-        // add the mutated paths to the list of possible paths
-        if visit_small_caves_twice && can_mutate_small_caves(&current_path) {
-            [
-                &firsts[..],
-                &mutators
-                    .into_iter()
-                    .map(|next| {
-                        let next_cell = next.upgrade().unwrap();
-                        let next_cave = next_cell.borrow();
-                        next_cave.traverse_all(&current_path, visit_small_caves_twice)
-                    })
-                    .flatten()
-                    .collect::<Vec<String>>()[..],
-            ]
-            .concat()
-        } else {
-            firsts
+                _ if !used_double_visit => self.count_from(next, visited, true, memo),
+                _ => 0,
+            };
         }
+        memo.insert((cave, visited, used_double_visit), count);
+        count
     }
-}
 
-fn find_type(name: &str) -> CaveType {
-    match name {
-        "start" => CaveType::Start,
-        "end" => CaveType::End,
-        a if 0 < a.len() && a.chars().nth(0).unwrap().is_uppercase() => CaveType::Big,
-        _ => CaveType::Small,
+    fn paths(&self, visit_small_cave_twice: bool) -> Paths<'_, 'a> {
+        let mut paths = Paths {
+            graph: self,
+            stack: Vec::new(),
+            visited: 0,
+            used_double_visit: !visit_small_cave_twice,
+        };
+        if let Some(start) = self.find(CaveType::Start) {
+            paths.stack.push(Frame {
+                cave: start,
+                next: 0,
+                double_visit: false,
+            });
+        }
+        paths
     }
 }
 
-// Returns true unless it finds a duplicate small cave
-fn can_mutate_small_caves(prevs: &Vec<&str>) -> bool {
-    let mut uniques: HashSet<&str> = HashSet::new();
-    for key in prevs {
-        if !uniques.insert(key) && find_type(key) == CaveType::Small {
-            return false;
+struct Frame {
+    cave: usize,
+    // Index of the next neighbour to try
+    next: usize,
+    // Whether this cave was entered using the double visit
+    double_visit: bool,
+}
+
+// Depth first enumeration of every path, one at a time
+struct Paths<'g, 'a> {
+    graph: &'g CaveGraph<'a>,
+    stack: Vec<Frame>,
+    visited: u64,
+    used_double_visit: bool,
+}
+
+impl<'g, 'a> Paths<'g, 'a> {
+    fn pop(&mut self) {
+        if let Some(frame) = self.stack.pop() {
+            if frame.double_visit {
+                self.used_double_visit = false;
+            } else {
+                self.visited &= !self.graph.masks[frame.cave];
+            }
         }
     }
-    true
-}
-
-fn build_graph(input: &str) -> HashMap<&str, Rc<RefCell<Cave>>> {
-    // Build graph:
-    let mut graph: HashMap<&str, Rc<RefCell<Cave>>> = HashMap::new();
-    for edge in input.lines() {
-        if let Some((a_key, b_key)) = edge.split_once('-') {
-            if !graph.contains_key(a_key) {
-                graph.insert(
-                    a_key,
-                    Rc::new(RefCell::new(Cave {
-                        name: a_key,
-                        cave_type: find_type(a_key),
-                        neighbours: Vec::new(),
-                    })),
-                );
-            }
-            if !graph.contains_key(b_key) {
-                graph.insert(
-                    b_key,
-                    Rc::new(RefCell::new(Cave {
-                        name: b_key,
-                        cave_type: find_type(b_key),
-                        neighbours: Vec::new(),
-                    })),
-                );
-            }
+}
 
-            let a_entry = graph.get(a_key).unwrap();
-            let b_entry = graph.get(b_key).unwrap();
-            if let (Ok(mut a_cave), Ok(mut b_cave)) =
-                (a_entry.try_borrow_mut(), b_entry.try_borrow_mut())
-            {
-                a_cave.neighbours.push(Rc::downgrade(b_entry));
-                b_cave.neighbours.push(Rc::downgrade(a_entry));
-            } else {
-                panic!("Failed to mutably borrow RefCell!");
+impl<'g, 'a> Iterator for Paths<'g, 'a> {
+    type Item = Vec<&'a str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let graph = self.graph;
+        loop {
+            let frame = self.stack.last_mut()?;
+            let cave = frame.cave;
+            if graph.types[cave] == CaveType::End {
+                let path = self.stack.iter().map(|f| graph.names[f.cave]).collect();
+                self.pop();
+                return Some(path);
             }
+
+            let next = match graph.neighbours[cave].get(frame.next) {
+                Some(next) => *next,
+                None => {
+                    self.pop();
+                    continue;
+                }
+            };
+            frame.next += 1;
+
+            let double_visit = match graph.types[next] {
+                CaveType::Start => continue,
+                CaveType::Big => false,
+                _ if self.visited & graph.masks[next] == 0 => {
+                    self.visited |= graph.masks[next];
+                    false
+                }
+                _ if !self.used_double_visit => {
+                    self.used_double_visit = true;
+                    true
+                }
+                _ => continue,
+            };
+            self.stack.push(Frame {
+                cave: next,
+                next: 0,
+                double_visit,
+            });
         }
     }
-    graph
 }
 
-fn solve1(input: &str) -> usize {
-    let graph = build_graph(input);
-    // Traverse graph:
-    let start = graph.get("start").unwrap();
-    let all_paths = start.borrow().traverse_all(&Vec::new(), false);
-    all_paths.len()
+#[cfg(test)]
+fn solve1(input: &str) -> u64 {
+    CaveGraph::new(input).unwrap().count_paths(false)
 }
 
-fn solve2(input: &str) -> usize {
-    let graph = build_graph(input);
-    // Traverse graph:
-    let start = graph.get("start").unwrap();
-    let all_paths = start.borrow().traverse_all(&Vec::new(), true);
-    all_paths.len()
+#[cfg(test)]
+fn solve2(input: &str) -> u64 {
+    CaveGraph::new(input).unwrap().count_paths(true)
 }
 
 fn main() {
     const INPUT: &str = include_str!("../inputs/12.txt");
-    println!("Part 1: path count: {}", solve1(INPUT));
+    let graph = match CaveGraph::new(INPUT) {
+        Ok(graph) => graph,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    println!("Part 1: path count: {}", graph.count_paths(false));
 
-    println!("Part 2: path count: {}", solve2(INPUT));
+    println!("Part 2: path count: {}", graph.count_paths(true));
+
+    if std::env::args().any(|a| a == "--paths") {
+        for path in graph.paths(true) {
+            println!("{}", path.join(","));
+        }
+    }
 }
 
 #[test]
@@ -171,10 +246,73 @@ fn test2() {
     assert_eq!(
         solve1("dc-end\nHN-start\nstart-kj\ndc-start\ndc-HN\nLN-dc\nHN-end\nkj-sa\nkj-HN\nkj-dc"),
         19
-    )
+    );
+    assert_eq!(
+        solve2("dc-end\nHN-start\nstart-kj\ndc-start\ndc-HN\nLN-dc\nHN-end\nkj-sa\nkj-HN\nkj-dc"),
+        103
+    );
 }
 
 #[test]
 fn test3() {
-    assert_eq!(solve1("fs-end\nhe-DX\nfs-he\nstart-DX\npj-DX\nend-zg\nzg-sl\nzg-pj\npj-he\nRW-he\nfs-DX\npj-RW\nzg-RW\nstart-pj\nhe-WI\nzg-he\npj-fs\nstart-RW"), 226)
+    assert_eq!(solve1("fs-end\nhe-DX\nfs-he\nstart-DX\npj-DX\nend-zg\nzg-sl\nzg-pj\npj-he\nRW-he\nfs-DX\npj-RW\nzg-RW\nstart-pj\nhe-WI\nzg-he\npj-fs\nstart-RW"), 226);
+    assert_eq!(solve2("fs-end\nhe-DX\nfs-he\nstart-DX\npj-DX\nend-zg\nzg-sl\nzg-pj\npj-he\nRW-he\nfs-DX\npj-RW\nzg-RW\nstart-pj\nhe-WI\nzg-he\npj-fs\nstart-RW"), 3509);
+}
+
+#[test]
+fn test_paths() {
+    const INPUT: &str = "start-A\nstart-b\nA-c\nA-b\nb-d\nA-end\nb-end";
+    let graph = CaveGraph::new(INPUT).unwrap();
+    let mut paths: Vec<String> = graph.paths(false).map(|p| p.join(",")).collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        vec![
+            "start,A,b,A,c,A,end",
+            "start,A,b,A,end",
+            "start,A,b,end",
+            "start,A,c,A,b,A,end",
+            "start,A,c,A,b,end",
+            "start,A,c,A,end",
+            "start,A,end",
+            "start,b,A,c,A,end",
+            "start,b,A,end",
+            "start,b,end",
+        ]
+    );
+}
+
+#[test]
+fn test_paths_match_count() {
+    const INPUT: &str = include_str!("../inputs/12.txt");
+    let graph = CaveGraph::new(INPUT).unwrap();
+    for twice in [false, true] {
+        let paths: HashSet<Vec<&str>> = graph.paths(twice).collect();
+        assert_eq!(paths.len() as u64, graph.count_paths(twice));
+        assert!(paths
+            .iter()
+            .all(|p| p.first() == Some(&"start") && p.last() == Some(&"end")));
+    }
+}
+
+#[test]
+fn test_many_caves() {
+    // Big caves don't need a bit, so any number of them is fine
+    let mut input = String::from("start-a\na-end\n");
+    for i in 0..100 {
+        input += &format!("a-B{}\n", i);
+    }
+    assert_eq!(CaveGraph::new(&input).unwrap().count_paths(true), 101);
+
+    // But small caves do
+    let mut input = String::from("start-end\n");
+    for i in 0..65 {
+        input += &format!("start-s{}\n", i);
+    }
+    assert_eq!(
+        CaveGraph::new(&input).err(),
+        Some(String::from("Too many small caves for the visited mask"))
+    );
+    input = input.replace("s64", "S64");
+    assert_eq!(CaveGraph::new(&input).unwrap().count_paths(true), 1);
 }
@@ -1,90 +1,198 @@
+use common::pbm;
 use std::collections::HashSet;
+use std::path::Path;
 
-#[derive(Debug)]
+type Dot = (u32, u32);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Fold {
+    // Folds the bottom half up along y = line
     Horizontal(u32),
-    Vertical(u32)
+    // Folds the right half left along x = line
+    Vertical(u32),
 }
 
-fn parse_input(input: &str) -> (HashSet<(u32, u32)>, Vec<Fold>) {
-    let dots: HashSet<(u32, u32)> = input.lines().take_while(|s|s.contains(',')).map(|s|{
-        let (a, b) = s.split_once(',').unwrap();
-        (a.parse().unwrap(), b.parse().unwrap())
-    }).collect();
-
-    let instructions: Vec<Fold> = input.lines().skip_while(|s|s.contains(',')).filter_map(|s|{
-        if let Some(i) = s.find(char::is_numeric) {
-            if let Ok(parsed) = &s[i..].parse() {
-                return if s.contains('y') {
-                    Some(Fold::Horizontal(*parsed))
-                } else {
-                    Some(Fold::Vertical(*parsed))
-                }
+fn parse_input(input: &str) -> (HashSet<Dot>, Vec<Fold>) {
+    let dots: HashSet<Dot> = input
+        .lines()
+        .take_while(|s| s.contains(','))
+        .filter_map(|s| {
+            let (a, b) = s.split_once(',')?;
+            Some((a.trim().parse().ok()?, b.trim().parse().ok()?))
+        })
+        .collect();
+
+    let instructions: Vec<Fold> = input
+        .lines()
+        .skip_while(|s| s.contains(','))
+        .filter_map(|s| {
+            let (axis, line) = s.strip_prefix("fold along ")?.split_once('=')?;
+            let line = line.trim().parse().ok()?;
+            match axis {
+                "y" => Some(Fold::Horizontal(line)),
+                "x" => Some(Fold::Vertical(line)),
+                _ => None,
             }
-        }
-        None
-    }).collect();
+        })
+        .collect();
 
     (dots, instructions)
 }
 
-#[test]
-fn test1() {
-    const INPUT: &str = "6,10\n0,14\n9,10\n0,3\n10,4\n4,11\n6,0\n6,12\n4,1\n0,13\n10,12\n3,4\n3,0\n8,4\n1,10\n2,14\n8,10\n9,0\n\nfold along y=7\nfold along x=5";
-    let (mut dots, instructions) = parse_input(INPUT);
-    println!("Dots before first instruction:\n{}", gridify(&dots));
-    dots = fold_paper(&dots, instructions.first().unwrap());
-    println!("Dots after first instruction:\n{}", gridify(&dots));
-    println!("... which is {} dots", dots.len());
-    assert_eq!(dots.len(), 17)
+// A sheet with dots on it. The size is kept track of separately from the
+// dots, since the paper can extend past them.
+#[derive(Clone, Debug, PartialEq)]
+struct Paper {
+    dots: HashSet<Dot>,
+    width: u32,
+    height: u32,
 }
 
-#[test]
-fn test2() {
-    const INPUT: &str = include_str!("../inputs/13.txt");
-    let (mut dots, instructions) = parse_input(INPUT);
-    for instruction in instructions {
-        dots = fold_paper(&dots, &instruction);
+impl Paper {
+    // Just large enough to fit every dot
+    fn new(dots: HashSet<Dot>) -> Paper {
+        let width = dots.iter().map(|d| d.0 + 1).max().unwrap_or(0);
+        let height = dots.iter().map(|d| d.1 + 1).max().unwrap_or(0);
+        Paper {
+            dots,
+            width,
+            height,
+        }
+    }
+
+    // Folding along a line that isn't in the middle leaves the longer half
+    // sticking out. If that is the half being folded over, the paper now
+    // starts further left (or up) than before, so everything is shifted to
+    // keep coordinates positive. Dots on the fold line disappear.
+    fn fold(&self, fold: Fold) -> Paper {
+        let (line, size) = match fold {
+            Fold::Horizontal(line) => (line, self.height),
+            Fold::Vertical(line) => (line, self.width),
+        };
+        let (kept, folded) = (line, size.saturating_sub(line + 1));
+        let shift = folded.saturating_sub(kept);
+        let new_size = kept.max(folded);
+
+        let flip = |c: u32| -> Option<u32> {
+            match c.cmp(&line) {
+                std::cmp::Ordering::Less => Some(c + shift),
+                std::cmp::Ordering::Equal => None,
+                std::cmp::Ordering::Greater => Some(2 * line + shift - c),
+            }
+        };
+        let dots = self
+            .dots
+            .iter()
+            .filter_map(|&(x, y)| match fold {
+                Fold::Horizontal(_) => Some((x, flip(y)?)),
+                Fold::Vertical(_) => Some((flip(x)?, y)),
+            })
+            .collect();
+
+        match fold {
+            Fold::Horizontal(_) => Paper {
+                dots,
+                width: self.width,
+                height: new_size,
+            },
+            Fold::Vertical(_) => Paper {
+                dots,
+                width: new_size,
+                height: self.height,
+            },
+        }
+    }
+
+    // Every state of the paper, starting with the unfolded one
+    fn fold_all(&self, folds: &[Fold]) -> Vec<Paper> {
+        let mut history = vec![self.clone()];
+        for &fold in folds {
+            let next = history.last().unwrap().fold(fold);
+            history.push(next);
+        }
+        history
+    }
+
+    fn render(&self) -> String {
+        let mut output = String::new();
+        for y in 0..self.height {
+            output.extend((0..self.width).map(|x| {
+                if self.dots.contains(&(x, y)) {
+                    '#'
+                } else {
+                    '.'
+                }
+            }));
+            output.push('\n');
+        }
+        output
+    }
+
+    fn to_pbm(&self) -> String {
+        pbm::to_pbm(self.width as usize, self.height as usize, |(x, y)| {
+            self.dots.contains(&(x as u32, y as u32))
+        })
     }
-    assert_eq!(dots.len(), 98)
+}
+
+fn dot_counts(history: &[Paper]) -> Vec<usize> {
+    history.iter().map(|p| p.dots.len()).collect()
 }
 
 fn main() {
     const INPUT: &str = include_str!("../inputs/13.txt");
-    let (mut dots, instructions) = parse_input(INPUT);
-    for instruction in instructions {
-        dots = fold_paper(&dots, &instruction);
+    let (dots, instructions) = parse_input(INPUT);
+    let history = Paper::new(dots).fold_all(&instructions);
+
+    for (i, count) in dot_counts(&history).iter().enumerate().skip(1) {
+        println!("Dots after fold {}: {}", i, count);
+    }
+    let paper = history.last().unwrap();
+    println!("Dots after all instructions:\n{}", paper.render());
+    println!("... which is {} dots", paper.dots.len());
+
+    // Optionally dump every fold as a PBM image to the given directory
+    if let Some(dir) = std::env::args().nth(1) {
+        pbm::export(Path::new(&dir), "13", history.iter().map(Paper::to_pbm)).unwrap();
     }
-    println!("Dots after all instructions:\n{}", gridify(&dots));
-    println!("... which is {} dots", dots.len());
 }
 
-fn fold_paper(dots: &HashSet<(u32, u32)>, instruction: &Fold) -> HashSet<(u32, u32)> {
-    let mut lefts: HashSet<(u32, u32)> = dots.iter().filter(|&(x,y)|{
-        match instruction {
-            Fold::Horizontal(line) => y < &line,
-            Fold::Vertical(line) => x < &line
-        }
-    }).cloned().collect();
+#[cfg(test)]
+const EXAMPLE: &str = "6,10\n0,14\n9,10\n0,3\n10,4\n4,11\n6,0\n6,12\n4,1\n0,13\n10,12\n3,4\n3,0\n8,4\n1,10\n2,14\n8,10\n9,0\n\nfold along y=7\nfold along x=5";
 
-    lefts.extend(dots.iter().filter_map(|&(x,y)|{
-        match instruction {
-            // Reflection of y: y => y - 2 * diff = y - 2 * (y - line) = y - 2y + 2line = 2line - y
-            Fold::Horizontal(line) => if *line < y { Some((x, line + line - y)) } else { None }
-            Fold::Vertical(line) => if *line < x { Some((line + line - x, y)) } else { None }
-        }
-    }));
-    lefts
+#[test]
+fn test1() {
+    let (dots, instructions) = parse_input(EXAMPLE);
+    let history = Paper::new(dots).fold_all(&instructions);
+    assert_eq!(dot_counts(&history), vec![18, 17, 16]);
+    assert_eq!(
+        history[2].render(),
+        "#####\n#...#\n#...#\n#...#\n#####\n.....\n.....\n"
+    );
 }
 
-fn gridify(dots: &HashSet<(u32, u32)>) -> String {
-    let max_x = dots.iter().map(|t|t.0).max().unwrap();
-    let max_y = dots.iter().map(|t|t.1).max().unwrap();
+#[test]
+fn test2() {
+    const INPUT: &str = include_str!("../inputs/13.txt");
+    let (dots, instructions) = parse_input(INPUT);
+    let history = Paper::new(dots).fold_all(&instructions);
+    assert_eq!(history[1].dots.len(), 684);
+    assert_eq!(history.last().unwrap().dots.len(), 98)
+}
 
-    let mut output = String::new();
-    for y in 0..=max_y {
-        output.extend((0..=max_x).into_iter().map(|x|if dots.contains(&(x, y)) { '#' } else { '.' }));
-        output.push('\n');
-    }
-    output
-}
\ No newline at end of file
+#[test]
+fn test_asymmetric_fold() {
+    // Folding at x=1 leaves three columns on the right to fold over the one
+    // on the left, so the old column 0 ends up at 2
+    let paper = Paper::new(HashSet::from([(0, 0), (3, 0), (4, 1)]));
+    assert_eq!(paper.width, 5);
+    let folded = paper.fold(Fold::Vertical(1));
+    assert_eq!(folded.width, 3);
+    assert_eq!(folded.render(), ".##\n#..\n");
+
+    // And the other way around, where the folded half fits
+    let paper = Paper::new(HashSet::from([(0, 0), (0, 3), (1, 4)]));
+    let folded = paper.fold(Fold::Horizontal(3));
+    assert_eq!(folded.height, 3);
+    assert_eq!(folded.render(), "#.\n..\n.#\n");
+}
@@ -1,115 +1,260 @@
 use itertools::Itertools;
-use std::collections::HashMap; // For next_tuple()
+use num::{BigUint, One, Zero};
+use std::collections::{BTreeMap, HashMap}; // For next_tuple()
 use std::time::Instant;
 
-fn main() {
-    const INPUT: &str = include_str!("../inputs/14.txt");
-    let polymer: Vec<char> = INPUT.lines().next().unwrap().chars().collect();
-
-    let rules: HashMap<(char, char), char> = INPUT
-        .lines()
-        .skip(2)
-        .filter_map(|l| {
-            let (l, r) = l.split_once(" -> ")?;
-            let pat = l.chars().next_tuple()?;
-            Some((pat, r.chars().next().unwrap()))
+// Sparse matrix stored as rows of (column, value) for the non-zero entries
+type SparseMatrix = Vec<Vec<(usize, BigUint)>>;
+
+fn sparse_mul(a: &SparseMatrix, b: &SparseMatrix) -> SparseMatrix {
+    a.iter()
+        .map(|row| {
+            let mut out: BTreeMap<usize, BigUint> = BTreeMap::new();
+            for (k, x) in row {
+                for (j, y) in &b[*k] {
+                    *out.entry(*j).or_default() += x * y;
+                }
+            }
+            out.into_iter().collect()
         })
-        .collect();
+        .collect()
+}
 
-    let now = Instant::now();
-    let mut frequency: HashMap<char, u64> = HashMap::new();
-    let mut it = polymer.windows(2);
-    while let Some(&[a,b]) = it.next() {
-        polymerize_count(a,b, 25, &rules, &mut frequency);
-    }
-    if let Some(v) = frequency.get_mut(polymer.last().unwrap()) {
-        *v += 1;
+fn sparse_pow(m: &SparseMatrix, mut exponent: u64) -> SparseMatrix {
+    let mut result: SparseMatrix = (0..m.len()).map(|i| vec![(i, BigUint::one())]).collect();
+    let mut base = m.clone();
+    while 0 < exponent {
+        if exponent & 1 == 1 {
+            result = sparse_mul(&result, &base);
+        }
+        base = sparse_mul(&base, &base);
+        exponent >>= 1;
     }
-    println!("Polymerization took {} seconds to execute", now.elapsed().as_secs());
+    result
+}
 
-    if let (Some(max), Some(min)) = (frequency.iter().map(|t|t.1).max(), frequency.iter().map(|t|t.1).min()) {
-        println!("Most common - least common = {}", max - min);
-    }
+// Only the amount of each pair matters, as every pair grows independently
+// into two new ones. Pair i turns into the pairs in row i of the transition.
+struct Polymer {
+    template: Vec<char>,
+    pairs: Vec<(char, char)>,
+    index: HashMap<(char, char), usize>,
+    transition: SparseMatrix,
 }
 
-#[test]
-fn test1() {
-    const INPUT: &str = "NNCB\n\nCH -> B\nHH -> N\nCB -> H\nNH -> C\nHB -> C\nHC -> B\nHN -> C\nNN -> C\nBH -> H\nNC -> B\nNB -> B\nBN -> B\nBB -> N\nBC -> B\nCC -> N\nCN -> C";
-    let polymer: Vec<char> = INPUT.lines().next().unwrap().chars().collect();
-
-    let rules: HashMap<(char, char), char> = INPUT
-        .lines()
-        .skip(2)
-        .filter_map(|l| {
-            let (l, r) = l.split_once(" -> ")?;
-            let pat = l.chars().next_tuple()?;
-            Some((pat, r.chars().next().unwrap()))
-        })
-        .collect();
+impl Polymer {
+    fn parse(input: &str) -> Polymer {
+        let template: Vec<char> = input.lines().next().unwrap().chars().collect();
+        let rules: HashMap<(char, char), char> = input
+            .lines()
+            .skip(2)
+            .filter_map(|l| {
+                let (l, r) = l.split_once(" -> ")?;
+                let pat = l.chars().next_tuple()?;
+                Some((pat, r.chars().next()?))
+            })
+            .collect();
 
-    let polymer = polymerize_n(&polymer, &rules, 10);
+        let pairs: Vec<(char, char)> = template
+            .iter()
+            .copied()
+            .tuple_windows()
+            .chain(
+                rules
+                    .iter()
+                    .flat_map(|(&(a, c), &b)| [(a, c), (a, b), (b, c)]),
+            )
+            .sorted()
+            .dedup()
+            .collect();
+        let index: HashMap<(char, char), usize> =
+            pairs.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+        let transition = pairs
+            .iter()
+            .map(|&(a, c)| match rules.get(&(a, c)) {
+                Some(&b) => vec![
+                    (index[&(a, b)], BigUint::one()),
+                    (index[&(b, c)], BigUint::one()),
+                ],
+                None => vec![(index[&(a, c)], BigUint::one())],
+            })
+            .collect();
 
-    let mut frequency: HashMap<char, u32> = HashMap::new();
-    for c in polymer.chars() {
-        *frequency.entry(c).or_default() += 1;
+        Polymer {
+            template,
+            pairs,
+            index,
+            transition,
+        }
     }
-    if let (Some(max), Some(min)) = (frequency.iter().map(|t|t.1).max(), frequency.iter().map(|t|t.1).min()) {
-        assert_eq!(max - min, 1588);
-    } else {
-        unreachable!();
+
+    fn initial_counts(&self) -> Vec<BigUint> {
+        let mut counts = vec![BigUint::zero(); self.pairs.len()];
+        for pair in self.template.iter().copied().tuple_windows() {
+            counts[self.index[&pair]] += 1u32;
+        }
+        counts
     }
-}
 
-fn polymerize(polymer: &Vec<char>, rules: &HashMap<(char, char), char>) -> Vec<char> {
-    if polymer.is_empty() {
-        panic!("Waii. ;_;");
-    } else {
-        // polymer
-        //     .windows(2)
-        //     .filter_map(|window| {
-        //         if let &[a, b, ..] = window {
-        //             if let Some(inserted) = rules.get(&(a, b)) {
-        //                 Some(vec![a, *inserted])
-        //             } else {
-        //                 Some(vec![a])
-        //             }
-        //         } else {
-        //             None
-        //         }
-        //     })
-        //     .flatten()
-        //     .chain(std::iter::once(*polymer.last().unwrap()))
-        //     .collect()
-        let mut out: Vec<char> = Vec::with_capacity(polymer.len() * 2);
-        let mut it = polymer.windows(2);
-        while let Some(&[a,b]) = it.next() {
-            out.push(a);
-            if let Some(inserted) = rules.get(&(a, b)) {
-                out.push(*inserted);
+    fn apply(&self, counts: &[BigUint], m: &SparseMatrix) -> Vec<BigUint> {
+        let mut out = vec![BigUint::zero(); counts.len()];
+        for (count, row) in counts.iter().zip(m) {
+            for (j, weight) in row {
+                out[*j] += count * weight;
             }
         }
-        out.push(*polymer.last().unwrap());
         out
     }
+
+    fn step(&self, counts: &[BigUint]) -> Vec<BigUint> {
+        self.apply(counts, &self.transition)
+    }
+
+    fn counts_after(&self, steps: u64) -> Vec<BigUint> {
+        self.apply(&self.initial_counts(), &sparse_pow(&self.transition, steps))
+    }
+
+    // Every element is the first of a pair, except for the very last one,
+    // which never changes.
+    fn histogram(&self, counts: &[BigUint]) -> BTreeMap<char, BigUint> {
+        let mut histogram: BTreeMap<char, BigUint> = self
+            .elements()
+            .into_iter()
+            .map(|c| (c, BigUint::zero()))
+            .collect();
+        for ((a, _), count) in self.pairs.iter().zip(counts) {
+            *histogram.get_mut(a).unwrap() += count;
+        }
+        if let Some(last) = self.template.last() {
+            *histogram.get_mut(last).unwrap() += 1u32;
+        }
+        histogram
+    }
+
+    fn histogram_after(&self, steps: u64) -> BTreeMap<char, BigUint> {
+        self.histogram(&self.counts_after(steps))
+    }
+
+    fn elements(&self) -> Vec<char> {
+        self.pairs
+            .iter()
+            .flat_map(|&(a, b)| [a, b])
+            .chain(self.template.iter().copied())
+            .sorted()
+            .dedup()
+            .collect()
+    }
+
+    // Element counts for every step up to and including the given one
+    fn csv(&self, steps: u32) -> String {
+        let mut out = format!("step,{}\n", self.elements().iter().join(","));
+        let mut counts = self.initial_counts();
+        for step in 0..=steps {
+            let histogram = self.histogram(&counts);
+            out += &format!("{},{}\n", step, histogram.values().join(","));
+            counts = self.step(&counts);
+        }
+        out
+    }
+}
+
+fn most_minus_least_common(histogram: &BTreeMap<char, BigUint>) -> BigUint {
+    match histogram.values().minmax().into_option() {
+        Some((min, max)) => max - min,
+        None => BigUint::zero(),
+    }
+}
+
+fn main() {
+    const INPUT: &str = include_str!("../inputs/14.txt");
+    let polymer = Polymer::parse(INPUT);
+
+    println!(
+        "Most common - least common after 10 steps = {}",
+        most_minus_least_common(&polymer.histogram_after(10))
+    );
+
+    let now = Instant::now();
+    let histogram = polymer.histogram_after(40);
+    println!(
+        "Polymerization took {} milliseconds to execute",
+        now.elapsed().as_millis()
+    );
+    println!(
+        "Most common - least common after 40 steps = {}",
+        most_minus_least_common(&histogram)
+    );
+
+    if std::env::args().any(|a| a == "--csv") {
+        print!("{}", polymer.csv(40));
+    }
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = "NNCB\n\nCH -> B\nHH -> N\nCB -> H\nNH -> C\nHB -> C\nHC -> B\nHN -> C\nNN -> C\nBH -> H\nNC -> B\nNB -> B\nBN -> B\nBB -> N\nBC -> B\nCC -> N\nCN -> C";
+
+#[cfg(test)]
+fn histogram_of(polymer: &str) -> BTreeMap<char, BigUint> {
+    let mut histogram: BTreeMap<char, BigUint> =
+        "BCHN".chars().map(|c| (c, BigUint::zero())).collect();
+    for c in polymer.chars() {
+        *histogram.get_mut(&c).unwrap() += 1u32;
+    }
+    histogram
+}
+
+#[test]
+fn test1() {
+    let polymer = Polymer::parse(EXAMPLE);
+    let histogram = polymer.histogram_after(10);
+    assert_eq!(histogram[&'B'], BigUint::from(1749u32));
+    assert_eq!(histogram[&'C'], BigUint::from(298u32));
+    assert_eq!(histogram[&'H'], BigUint::from(161u32));
+    assert_eq!(histogram[&'N'], BigUint::from(865u32));
+    assert_eq!(most_minus_least_common(&histogram), BigUint::from(1588u32));
+}
+
+#[test]
+fn test_40_steps() {
+    let polymer = Polymer::parse(EXAMPLE);
+    assert_eq!(
+        most_minus_least_common(&polymer.histogram_after(40)),
+        BigUint::from(2188189693529u64)
+    );
 }
 
-fn polymerize_n(polymer: &Vec<char>, rules: &HashMap<(char, char), char>, n: u32) -> String {
-    if n == 0 {
-        return polymer.into_iter().collect::<String>();
-    } else {
-        polymerize_n(&polymerize(&polymer, &rules), &rules, n-1)
+#[test]
+fn test_first_steps() {
+    let polymer = Polymer::parse(EXAMPLE);
+    let expected = [
+        "NNCB",
+        "NCNBCHB",
+        "NBCCNBBBCBHCB",
+        "NBBBCNCCNBBNBNBBCHBHHBCHB",
+    ];
+    let mut counts = polymer.initial_counts();
+    for (step, s) in expected.iter().enumerate() {
+        assert_eq!(polymer.histogram(&counts), histogram_of(s));
+        assert_eq!(polymer.histogram_after(step as u64), histogram_of(s));
+        counts = polymer.step(&counts);
     }
 }
 
-fn polymerize_count(a: char, c: char, depth: u32, rules: &HashMap<(char, char), char>, count: &mut HashMap<char, u64>) {
-    if depth == 0 {
-        *count.entry(a).or_default() += 1;
-    } else {
-        if let Some(b) = rules.get(&(a, c)) {
-            polymerize_count(a, *b, depth - 1, &rules, count);
-            polymerize_count(*b, c, depth - 1, &rules, count);
-        } else {
-            *count.entry(a).or_default() += 1;
+#[test]
+fn test_power_matches_stepping() {
+    let polymer = Polymer::parse(include_str!("../inputs/14.txt"));
+    let mut counts = polymer.initial_counts();
+    for steps in 0..25 {
+        if [0, 1, 2, 7, 16, 24].contains(&steps) {
+            assert_eq!(polymer.counts_after(steps), counts);
         }
+        counts = polymer.step(&counts);
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_csv() {
+    let polymer = Polymer::parse(EXAMPLE);
+    let csv = polymer.csv(2);
+    assert_eq!(csv, "step,B,C,H,N\n0,1,1,0,2\n1,2,2,1,2\n2,6,4,1,2\n");
+}
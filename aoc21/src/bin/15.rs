@@ -1,205 +1,287 @@
-use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap};
-
-type Grid = Vec<Vec<u8>>;
-
 fn main() {
     const INPUT: &str = include_str!("../inputs/15.txt");
-    // const INPUT: &str = "1163751742\n1381373672\n2136511328\n3694931569\n7463417111\n1319128137\n1359912421\n3125421639\n1293138521\n2311944581";
-    let grid: Grid = INPUT
-        .lines()
-        .map(|l| {
-            l.chars()
-                .map(|c| c.to_digit(10).unwrap().try_into().unwrap())
-                .collect()
-        })
-        .collect();
+    let cave = match Cave::parse(INPUT) {
+        Ok(cave) => cave,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let args: Vec<String> = std::env::args().collect();
+    // Prints the cave with the route through it as well
+    let draw = args.iter().any(|a| a == "--draw");
+
+    for (part, tiles) in [("Path's", 1), ("Big grid's path's", 5)] {
+        let map = cave.tiled(tiles);
+        let path = map.lowest_risk_path().unwrap();
+        if draw {
+            println!("{}", map.render(&path));
+        }
+        println!("{} risk sum is {}", part, path.risk);
+    }
+}
 
-    let (x_max, y_max) = find_grid_max(&grid);
-    let path = djikstra(&grid, (0, 0), (x_max - 1, y_max - 1));
-    // println!("Path is: {:?}", path.iter().map(|n|n.pos).collect::<Vec<(usize, usize)>>());
-    println!("Path's risk sum is {}", path.iter().map(|n|n.cost).sum::<u32>());
+type Pos = (usize, usize);
 
-    // Part 2:
-    let big_grid = gen_large_grid(&grid);
-    // println!("Big grid: \n{}", big_grid.iter().map(|l|l.iter().map(|n|n.to_string()).collect::<String>()).collect::<Vec<String>>().join("\n"));
-    let (x_max, y_max) = find_grid_max(&big_grid);
-    let path = djikstra(&big_grid, (0, 0), (x_max - 1, y_max - 1));
-    println!("Big grid's path's risk sum is {}", path.iter().map(|n|n.cost).sum::<u32>());
+// Risk levels are always 1 to 9, which is what makes the bucket queue below
+// work and the tiling wrap around.
+const MAX_RISK: u8 = 9;
 
+struct Cave {
+    width: usize,
+    height: usize,
+    risks: Vec<u8>,
 }
 
-fn find_grid_max(grid: &Grid) -> (usize, usize) {
-    (grid.len(), grid.iter().nth(0).unwrap().len())
+impl Cave {
+    fn parse(input: &str) -> Result<Cave, String> {
+        let lines: Vec<(usize, &str)> = input
+            .lines()
+            .map(|l| l.trim())
+            .enumerate()
+            .filter(|(_, l)| !l.is_empty())
+            .collect();
+        let width = lines.first().map_or(0, |(_, l)| l.len());
+        let mut risks = Vec::with_capacity(width * lines.len());
+        for &(i, line) in &lines {
+            if line.len() != width {
+                return Err(format!(
+                    "Line {} is {} wide, expected {}",
+                    i + 1,
+                    line.len(),
+                    width
+                ));
+            }
+            for c in line.chars() {
+                match c.to_digit(10) {
+                    Some(d @ 1..=9) => risks.push(d as u8),
+                    _ => return Err(format!("Line {}: '{}' is not a risk level", i + 1, c)),
+                }
+            }
+        }
+        Ok(Cave {
+            width,
+            height: lines.len(),
+            risks,
+        })
+    }
+
+    fn tiled(&self, tiles: usize) -> Tiled<'_> {
+        Tiled { cave: self, tiles }
+    }
 }
 
-#[derive(Eq, Clone, Copy, Debug)]
-struct Node {
-    cost: u32,
-    distance: u32,
-    pos: (usize, usize),
-    prev: (usize, usize),
+// The cave repeated tiles times in both directions, where every tile to the
+// right or down is one riskier than the last (with 9 wrapping back to 1).
+// Risks are worked out when asked for rather than stored.
+struct Tiled<'a> {
+    cave: &'a Cave,
+    tiles: usize,
 }
 
-impl PartialEq for Node {
-    fn eq(&self, other: &Self) -> bool {
-        self.distance == other.distance
-    }
+#[derive(Debug, PartialEq)]
+struct Path {
+    // Not counting the start, which is never entered
+    risk: u32,
+    // From start to end, both included
+    cells: Vec<Pos>,
 }
 
-impl PartialOrd for Node {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        other.distance.partial_cmp(&self.distance)
+impl Tiled<'_> {
+    fn width(&self) -> usize {
+        self.cave.width * self.tiles
     }
-}
 
-impl Ord for Node {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other.distance.cmp(&self.distance)
+    fn height(&self) -> usize {
+        self.cave.height * self.tiles
     }
-}
 
-fn djikstra(grid: &Grid, start: (usize, usize), end: (usize, usize)) -> Vec<Node> {
-    let mut checked = HashMap::new();
-    let mut search_heap = BinaryHeap::new();
-
-    // Initial nodes:
-    checked.insert(
-        start,
-        Node {
-            cost: 0,
-            distance: 0,
-            pos: start,
-            prev: start,
-        },
-    );
-    for neighbour in neighbour_indices(start) {
-        if let Some(coord) = neighbour {
-            if let Some(value) = index(grid, coord) {
-                let node = Node {
-                    cost: u32::from(*value),
-                    distance: u32::from(*value),
-                    pos: coord,
-                    prev: start,
-                };
-                if checked.insert(coord, node).is_none() {
-                    search_heap.push(node);
-                }
-            }
+    fn risk(&self, (x, y): Pos) -> Option<u8> {
+        if self.width() <= x || self.height() <= y {
+            return None;
         }
+        let (w, h) = (self.cave.width, self.cave.height);
+        let base = self.cave.risks[(y % h) * w + x % w];
+        let increase = ((x / w + y / h) % MAX_RISK as usize) as u8;
+        Some((base - 1 + increase) % MAX_RISK + 1)
     }
 
-    // Main logic:
-    while let Some(mut next) = search_heap.pop() {
-        if next.pos == end {
-            let mut rets = Vec::from([next]);
-            let mut prev = next.prev;
-            while prev != start {
-                next = *checked.get(&prev).unwrap();
-                prev = next.prev;
-                rets.push(next);
-            }
+    fn neighbours(&self, (x, y): Pos) -> impl Iterator<Item = Pos> + '_ {
+        [(-1, 0), (1, 0), (0, -1), (0, 1)]
+            .into_iter()
+            .filter_map(move |(dx, dy)| {
+                let pos = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+                self.risk(pos).map(|_| pos)
+            })
+    }
 
-            return rets.into_iter().rev().collect();
+    // From the top left to the bottom right
+    fn lowest_risk_path(&self) -> Option<Path> {
+        if self.width() == 0 || self.height() == 0 {
+            return None;
         }
+        self.shortest_path((0, 0), (self.width() - 1, self.height() - 1))
+    }
+
+    // Dijkstra's algorithm, with a bucket queue (Dial's algorithm) instead of
+    // a heap. Every cell in the queue is at most MAX_RISK further away than
+    // the one being looked at, so a ring of MAX_RISK + 1 buckets, indexed by
+    // distance, is enough to always find the closest one.
+    fn shortest_path(&self, start: Pos, end: Pos) -> Option<Path> {
+        self.risk(start)?;
+        self.risk(end)?;
+        let width = self.width();
+        let index = |(x, y): Pos| y * width + x;
+        let mut distances = vec![u32::MAX; width * self.height()];
+        let mut previous: Vec<Option<Pos>> = vec![None; distances.len()];
+        let ring = MAX_RISK as usize + 1;
+        let mut buckets: Vec<Vec<Pos>> = vec![Vec::new(); ring];
+        let mut queued = 1;
 
-        for neighbour in neighbour_indices(next.pos) {
-            if let Some(coord) = neighbour {
-                if let Some(value) = index(grid, coord) {
-                    let node = Node {
-                        cost: u32::from(*value),
-                        distance: next.distance + u32::from(*value),
-                        pos: coord,
-                        prev: next.pos,
-                    };
-                    // Cannot use insert to check, because it also updates the entry
-                    // (can use try_insert whenever it becomes stable)
-                    if !checked.contains_key(&coord) {
-                        checked.insert(coord, node);
-                        search_heap.push(node);
-                    }
+        distances[index(start)] = 0;
+        buckets[0].push(start);
+        let mut distance = 0;
+        while 0 < queued {
+            let bucket = distance as usize % ring;
+            let Some(pos) = buckets[bucket].pop() else {
+                distance += 1;
+                continue;
+            };
+            queued -= 1;
+            // Already reached some cheaper way since being queued
+            if distances[index(pos)] < distance {
+                continue;
+            }
+            if pos == end {
+                break;
+            }
+            for next in self.neighbours(pos) {
+                let d = distance + u32::from(self.risk(next).unwrap());
+                if d < distances[index(next)] {
+                    distances[index(next)] = d;
+                    previous[index(next)] = Some(pos);
+                    buckets[d as usize % ring].push(next);
+                    queued += 1;
                 }
             }
         }
+
+        let risk = distances[index(end)];
+        if risk == u32::MAX {
+            return None;
+        }
+        let mut cells = vec![end];
+        while let Some(pos) = previous[index(*cells.last().unwrap())] {
+            cells.push(pos);
+        }
+        cells.reverse();
+        Some(Path { risk, cells })
     }
 
-    vec![]
+    // The risk levels along the path, with everything else blanked out
+    fn render(&self, path: &Path) -> String {
+        let mut on_path = vec![false; self.width() * self.height()];
+        for &(x, y) in &path.cells {
+            on_path[y * self.width() + x] = true;
+        }
+        let mut output = String::new();
+        for y in 0..self.height() {
+            output.extend((0..self.width()).map(|x| {
+                if on_path[y * self.width() + x] {
+                    char::from(b'0' + self.risk((x, y)).unwrap())
+                } else {
+                    '.'
+                }
+            }));
+            output.push('\n');
+        }
+        output
+    }
 }
 
-fn neighbour_indices((i, j): (usize, usize)) -> [Option<(usize, usize)>; 4] {
-    [
-        safe_add((i, j), (-1, 0)),
-        safe_add((i, j), (1, 0)),
-        safe_add((i, j), (0, -1)),
-        safe_add((i, j), (0, 1)),
-    ]
-}
+#[cfg(test)]
+const EXAMPLE: &str = "1163751742\n1381373672\n2136511328\n3694931569\n7463417111\n1319128137\n1359912421\n3125421639\n1293138521\n2311944581";
 
-fn safe_add(a: (usize, usize), b: (i32, i32)) -> Option<(usize, usize)> {
-    let l = usize::try_from(i32::try_from(a.0).ok()? + b.0).ok()?;
-    let r = usize::try_from(i32::try_from(a.1).ok()? + b.1).ok()?;
-    Some((l, r))
+#[cfg(test)]
+fn check_path(map: &Tiled, path: &Path) {
+    // Every step is to a neighbour, and the risks add up
+    assert_eq!(path.cells.first(), Some(&(0, 0)));
+    assert_eq!(
+        path.cells.last(),
+        Some(&(map.width() - 1, map.height() - 1))
+    );
+    for pair in path.cells.windows(2) {
+        assert!(map.neighbours(pair[0]).any(|p| p == pair[1]));
+    }
+    let risk: u32 = path.cells[1..]
+        .iter()
+        .map(|&p| u32::from(map.risk(p).unwrap()))
+        .sum();
+    assert_eq!(risk, path.risk);
 }
 
-fn index(grid: &Grid, (i, j): (usize, usize)) -> Option<&u8> {
-    grid.get(j)?.get(i)
+#[test]
+fn test1() {
+    let cave = Cave::parse(EXAMPLE).unwrap();
+    let map = cave.tiled(1);
+    let path = map.lowest_risk_path().unwrap();
+    assert_eq!(path.risk, 40);
+    check_path(&map, &path);
+    assert_eq!(map.render(&path).lines().next(), Some("1........."));
+    assert_eq!(map.render(&path).lines().last(), Some(".........1"));
 }
 
-fn gen_large_grid(small_grid: &Grid) -> Grid {
-    let small_grid_size = find_grid_max(small_grid);
-    let mut grid = Vec::with_capacity(small_grid_size.1 * 5);
-    for y in 0..5 {
-        for l in small_grid {
-            let mut newline = Vec::with_capacity(small_grid_size.0 * 5);
-            for x in 0..5 {
-                newline.extend(l.iter().map(|v|{
-                    let b = v + x + y;
-                    if 9 < b {
-                        b - 9
-                    } else {
-                        b
-                    }
-                }));
-            }
-            grid.push(newline);
-        }
-    }
-    grid
+#[test]
+fn test2() {
+    let cave = Cave::parse(EXAMPLE).unwrap();
+    let map = cave.tiled(5);
+    assert_eq!((map.width(), map.height()), (50, 50));
+    let path = map.lowest_risk_path().unwrap();
+    assert_eq!(path.risk, 315);
+    check_path(&map, &path);
 }
 
 #[test]
-fn test1() {
-    const INPUT: &str = "1163751742\n1381373672\n2136511328\n3694931569\n7463417111\n1319128137\n1359912421\n3125421639\n1293138521\n2311944581";
-    let grid: Grid = INPUT
-        .lines()
-        .map(|l| {
-            l.chars()
-                .map(|c| c.to_digit(10).unwrap().try_into().unwrap())
-                .collect()
-        })
-        .collect();
-
-    let (x_max, y_max) = find_grid_max(&grid);
-    let path = djikstra(&grid, (0, 0), (x_max - 1, y_max - 1));
+fn test_tiling() {
+    let cave = Cave::parse(EXAMPLE).unwrap();
+    let map = cave.tiled(5);
+    let row = |y: usize| -> String {
+        (0..map.width())
+            .map(|x| char::from(b'0' + map.risk((x, y)).unwrap()))
+            .collect()
+    };
+    assert_eq!(row(0), "11637517422274862853338597396444961841755517295286");
+    assert_eq!(
+        row(49),
+        "67554889357866599146897761125791887223681299833479"
+    );
+    assert_eq!(map.risk((50, 0)), None);
 
-    assert_eq!(path.iter().map(|n|n.cost).sum::<u32>(), 40);
+    // Far enough out that 9 wraps around more than once
+    let map = Cave::parse("9").unwrap();
+    let map = map.tiled(20);
+    assert_eq!(map.risk((0, 0)), Some(9));
+    assert_eq!(map.risk((1, 0)), Some(1));
+    assert_eq!(map.risk((10, 9)), Some(1));
+    assert_eq!(map.risk((19, 19)), Some(2));
 }
 
 #[test]
-fn test2() {
-    const INPUT: &str = "1163751742\n1381373672\n2136511328\n3694931569\n7463417111\n1319128137\n1359912421\n3125421639\n1293138521\n2311944581";
-    let mut grid: Grid = INPUT
-        .lines()
-        .map(|l| {
-            l.chars()
-                .map(|c| c.to_digit(10).unwrap().try_into().unwrap())
-                .collect()
-        })
-        .collect();
-
-    grid = gen_large_grid(&grid);
-    let (x_max, y_max) = find_grid_max(&grid);
-    let path = djikstra(&grid, (0, 0), (x_max - 1, y_max - 1));
+fn test_input() {
+    let cave = Cave::parse(include_str!("../inputs/15.txt")).unwrap();
+    assert_eq!(cave.tiled(1).lowest_risk_path().unwrap().risk, 621);
+    assert_eq!(cave.tiled(5).lowest_risk_path().unwrap().risk, 2904);
+}
 
-    assert_eq!(path.iter().map(|n|n.cost).sum::<u32>(), 315);
-}
\ No newline at end of file
+#[test]
+fn test_bad_input() {
+    assert_eq!(
+        Cave::parse("123\n45").err(),
+        Some("Line 2 is 2 wide, expected 3".to_string())
+    );
+    assert_eq!(
+        Cave::parse("123\n405").err(),
+        Some("Line 2: '0' is not a risk level".to_string())
+    );
+    assert_eq!(Cave::parse("").unwrap().tiled(5).lowest_risk_path(), None);
+}
@@ -1,67 +1,208 @@
-use std::collections::HashSet;
+use regex::Regex;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Target {
+    x: (i64, i64),
+    y: (i64, i64),
+}
+
+fn parse_input(input: &str) -> Option<Target> {
+    let re = Regex::new(r"x=(-?\d+)\.\.(-?\d+), y=(-?\d+)\.\.(-?\d+)").unwrap();
+    let cap = re.captures(input)?;
+    let n = |i: usize| cap[i].parse::<i64>().ok();
+    let (x0, x1, y0, y1) = (n(1)?, n(2)?, n(3)?, n(4)?);
+    Some(Target {
+        x: (x0.min(x1), x0.max(x1)),
+        y: (y0.min(y1), y0.max(y1)),
+    })
+}
 
 fn main() {
-    // Too lazy to even parse the input probely correctly on this one. Can just hardcode:
-    // find_velocites((20, 30), (-10, -5), 100);
-    find_velocites((119, 176), (-141, -84), 500);
-}
-
-fn find_velocites(xminmax: (i64, i64), yminmax: (i64, i64), search_space: i64) {
-    let mut uniques = HashSet::new();
-    let mut top_highest = 0;
-    for y in -search_space..search_space {
-        let ts = valid_y(y, yminmax, search_space);
-        for (t, highest) in ts {
-            let xs = find_xs(t, xminmax, search_space);
-            for x in xs {
-                uniques.insert((x, y));
-                if top_highest < highest {
-                    top_highest = highest;
-                    println!("New highest point {} achieved with velocity ({},{})", top_highest, x, y);
-                }
-            }
-        }
-    }
+    const INPUT: &str = include_str!("../inputs/17.txt");
+    let target = parse_input(INPUT).unwrap();
+
+    println!(
+        "Highest point reached while still hitting the target: {}",
+        max_height(&target).unwrap()
+    );
+    println!(
+        "Distinct velocities hitting the target: {}",
+        hitting_velocities(&target).unwrap().len()
+    );
+}
+
+// Position after t steps, which for x stops changing once drag has slowed
+// the probe to a halt.
+fn x_at(v: i64, t: i64) -> i64 {
+    let n = t.min(v.abs());
+    v.signum() * (n * v.abs() - n * (n - 1) / 2)
+}
 
-    println!("Distinct velocities: {}. Those are: {}", uniques.len(), uniques.into_iter().map(|(x,y)|format!("({},{})", x, y)).collect::<Vec<String>>().join(","));
+fn y_at(v: i64, t: i64) -> i64 {
+    t * v - t * (t - 1) / 2
 }
 
-fn valid_y(mut v: i64, (y_min, y_max): (i64, i64), search_space: i64) -> Vec<(i64, i64)> {
-    let mut ts = Vec::new();
-    let mut y = 0;
-    let mut highest = 0;
-    for t in 0..search_space {
-        if y < y_min {
+fn highest_point(vy: i64) -> i64 {
+    if 0 < vy {
+        vy * (vy + 1) / 2
+    } else {
+        0
+    }
+}
+
+// Steps at which a probe launched with vertical velocity vy is within the
+// target's y range
+fn y_steps(vy: i64, (y_min, y_max): (i64, i64)) -> Vec<i64> {
+    let mut steps = Vec::new();
+    for t in 1.. {
+        let y = y_at(vy, t);
+        // Only falling from here on
+        if y < y_min && vy < t {
             break;
-        } else if y <= y_max {
-            ts.push((t, highest));
         }
-        y += v;
-        if highest < y {
-            highest = y;
+        if y_min <= y && y <= y_max {
+            steps.push(t);
         }
-        v -= 1;
     }
-    ts
-}
-
-fn find_xs(t: i64, (x_min, x_max): (i64, i64), search_space: i64) -> Vec<i64> {
-    let mut xs = Vec::new();
-    for v0 in 0..search_space {
-        let mut v = v0;
-        let mut x = 0;
-        for _ in 0..t {
-            x += v;
-            if v != 0 {
-                v -= 1;
+    steps
+}
+
+// Where a probe launched with horizontal velocity vx comes to a halt
+fn x_final(vx: i64) -> i64 {
+    vx.signum() * highest_point(vx.abs())
+}
+
+// Every upwards launch comes back down through y=0 eventually, so if that is
+// in the target and some probe stops moving sideways within it, there is no
+// limit on how high it can be launched.
+fn unbounded(target: &Target) -> bool {
+    let (x_min, x_max) = target.x;
+    let (y_min, y_max) = target.y;
+    y_min <= 0
+        && 0 <= y_max
+        && (x_min.min(0)..=x_max.max(0)).any(|vx| (x_min..=x_max).contains(&x_final(vx)))
+}
+
+// None when there are infinitely many
+fn hitting_velocities(target: &Target) -> Option<Vec<(i64, i64)>> {
+    if unbounded(target) {
+        return None;
+    }
+    let (x_min, x_max) = target.x;
+    let (y_min, y_max) = target.y;
+    // Any faster and the first step already jumps past the target. Going up,
+    // the probe comes back past y=0 with velocity -vy - 1, so the same goes
+    // for y. Targets spanning y=0 can also be hit on the way back down, but
+    // only while the probe is still moving sideways, which takes at most as
+    // many steps as the fastest vx.
+    let vxs = x_min.min(0)..=x_max.max(0);
+    let mut vy_max = y_min.abs().max(y_max.abs());
+    if y_min <= 0 && 0 <= y_max {
+        vy_max = vy_max.max(x_min.abs()).max(x_max.abs());
+    }
+    let vys = y_min.min(0)..=vy_max;
+
+    let mut velocities = Vec::new();
+    for vy in vys {
+        let steps = y_steps(vy, target.y);
+        if steps.is_empty() {
+            continue;
+        }
+        for vx in vxs.clone() {
+            if steps.iter().any(|&t| {
+                let x = x_at(vx, t);
+                x_min <= x && x <= x_max
+            }) {
+                velocities.push((vx, vy));
             }
         }
-        if x < x_min {
-            continue;
-        } else if x <= x_max {
-            xs.push(v0);
-            continue;
+    }
+    Some(velocities)
+}
+
+// None if the target is never hit, or there is no limit to how high
+fn max_height(target: &Target) -> Option<i64> {
+    hitting_velocities(target)?
+        .iter()
+        .map(|&(_, vy)| highest_point(vy))
+        .max()
+}
+
+#[cfg(test)]
+fn brute_force(target: &Target, search_space: i64) -> Vec<(i64, i64)> {
+    let mut velocities = Vec::new();
+    for vx in -search_space..=search_space {
+        for vy in -search_space..=search_space {
+            let (mut x, mut y, mut v) = (0, 0, (vx, vy));
+            for _ in 0..4 * search_space {
+                x += v.0;
+                y += v.1;
+                v = (v.0 - v.0.signum(), v.1 - 1);
+                if target.x.0 <= x && x <= target.x.1 && target.y.0 <= y && y <= target.y.1 {
+                    velocities.push((vx, vy));
+                    break;
+                }
+            }
         }
     }
-    xs
-}
\ No newline at end of file
+    velocities
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(
+        parse_input("target area: x=20..30, y=-10..-5"),
+        Some(Target {
+            x: (20, 30),
+            y: (-10, -5)
+        })
+    );
+    assert_eq!(parse_input("target area: x=20..30"), None);
+}
+
+#[test]
+fn test_example() {
+    let target = parse_input("target area: x=20..30, y=-10..-5").unwrap();
+    assert_eq!(max_height(&target), Some(45));
+    assert_eq!(hitting_velocities(&target).unwrap().len(), 112);
+}
+
+#[test]
+fn test_mirrored_target() {
+    let target = parse_input("target area: x=-30..-20, y=-10..-5").unwrap();
+    assert_eq!(max_height(&target), Some(45));
+    assert_eq!(hitting_velocities(&target).unwrap().len(), 112);
+}
+
+#[test]
+fn test_odd_targets() {
+    for input in [
+        "target area: x=20..30, y=5..10",
+        "target area: x=-5..8, y=-10..-5",
+        "target area: x=-30..-20, y=3..12",
+        "target area: x=-3..3, y=-6..-2",
+        // Spanning y=0, but every probe that gets there has stopped elsewhere
+        "target area: x=8..9, y=-2..2",
+        "target area: x=-9..-8, y=0..4",
+    ] {
+        let target = parse_input(input).unwrap();
+        let mut expected = brute_force(&target, 60);
+        let mut velocities = hitting_velocities(&target).unwrap();
+        expected.sort();
+        velocities.sort();
+        assert_eq!(velocities, expected, "{}", input);
+    }
+}
+
+#[test]
+fn test_unbounded_targets() {
+    for input in [
+        "target area: x=-3..3, y=-2..2",
+        "target area: x=20..30, y=-10..0",
+        "target area: x=-10..-10, y=0..5",
+    ] {
+        let target = parse_input(input).unwrap();
+        assert_eq!(hitting_velocities(&target), None, "{}", input);
+        assert_eq!(max_height(&target), None, "{}", input);
+    }
+}
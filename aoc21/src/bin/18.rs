@@ -1,194 +1,371 @@
-use num::Integer; // Useful funcions, but weird implementations
-use std::rc::Rc;
+use itertools::Itertools;
+use std::fmt;
+use std::ops::Add;
+use std::str::FromStr;
 
-#[derive(Debug)]
-enum NumberType {
-    Regular(u64),
-    Pair(Rc<Number>),
-}
-
-use NumberType::{Pair, Regular};
+// A snailfish number stored as its regular numbers in reading order, each
+// paired with how many pairs it's nested inside. Exploding and splitting
+// only ever touch neighbouring regular numbers, so there's no need for a tree.
+#[derive(Clone, PartialEq, Debug, Default)]
+struct Number(Vec<(u64, u32)>);
 
-#[derive(Debug)]
-struct Number {
-    l: NumberType,
-    r: NumberType,
-}
+impl Number {
+    fn explode(&mut self) -> bool {
+        let i = match self.0.iter().position(|&(_, depth)| 4 < depth) {
+            Some(i) => i,
+            None => return false,
+        };
+        let ((a, depth), (b, _)) = (self.0[i], self.0[i + 1]);
+        if let Some((n, _)) = i.checked_sub(1).and_then(|j| self.0.get_mut(j)) {
+            *n += a;
+        }
+        if let Some((n, _)) = self.0.get_mut(i + 2) {
+            *n += b;
+        }
+        self.0.splice(i..i + 2, [(0, depth - 1)]);
+        true
+    }
 
-#[derive(Debug)]
-enum ReductionResult {
-    Explotion(u64, u64),
-    Split,
-    Nothing,
-}
+    fn split(&mut self) -> bool {
+        let i = match self.0.iter().position(|&(n, _)| 10 <= n) {
+            Some(i) => i,
+            None => return false,
+        };
+        let (n, depth) = self.0[i];
+        self.0
+            .splice(i..i + 1, [(n / 2, depth + 1), (n.div_ceil(2), depth + 1)]);
+        true
+    }
 
-impl Number {
-    fn reduce(&mut self, depth: i64) -> ReductionResult {
-        use ReductionResult::{Explotion, Nothing, Split};
-        let mut should_explode = false;
-        if let Pair(childref) = &mut self.l {
-            if let Some(child) = Rc::get_mut(childref) {
-                if 2 < depth {
-                    if let (Regular(_), Regular(_)) = (&child.l, &child.r) {
-                        should_explode = true;
-                    }
-                }
+    fn reduce(&mut self) {
+        while self.explode() || self.split() {}
+    }
 
-                if !should_explode {
-                    match child.reduce(depth + 1) {
-                        Explotion(le, re) => {
-                            // Pass the right value right and the left value upwards
-                            if re != 0 {
-                                match &mut self.r {
-                                    Pair(childref) => {
-                                        pass_value_left(Rc::get_mut(childref).unwrap(), re)
-                                    }
-                                    Regular(n) => *n += re,
-                                }
-                            }
-                            return Explotion(le, 0);
-                        }
-                        Split => return Split,
-                        Nothing => (),
-                    }
+    fn magnitude(&self) -> u64 {
+        let mut values = self.0.clone();
+        let max_depth = values.iter().map(|&(_, depth)| depth).max().unwrap_or(0);
+        // Once everything deeper has been collapsed, the regular numbers at a
+        // given depth always come in consecutive pairs.
+        for depth in (1..=max_depth).rev() {
+            let mut collapsed = Vec::with_capacity(values.len());
+            let mut iter = values.into_iter();
+            while let Some((a, d)) = iter.next() {
+                if d == depth {
+                    let (b, _) = iter.next().unwrap();
+                    collapsed.push((3 * a + 2 * b, depth - 1));
+                } else {
+                    collapsed.push((a, d));
                 }
             }
+            values = collapsed;
         }
+        values.first().map(|&(n, _)| n).unwrap_or(0)
+    }
 
-        if should_explode {
-            if let Explotion(a, b) = self.explode() {
-                return Explotion(a, b);
-            }
+    fn fmt_element(&self, f: &mut fmt::Formatter, i: &mut usize, depth: u32) -> fmt::Result {
+        let (n, d) = self.0[*i];
+        if d == depth {
+            *i += 1;
+            write!(f, "{}", n)
+        } else {
+            write!(f, "[")?;
+            self.fmt_element(f, i, depth + 1)?;
+            write!(f, ",")?;
+            self.fmt_element(f, i, depth + 1)?;
+            write!(f, "]")
         }
+    }
+}
+
+// What the parser will accept next
+#[derive(Clone, Copy, PartialEq)]
+enum Expect {
+    Element,
+    Comma,
+    Close,
+    End,
+}
+
+impl FromStr for Number {
+    type Err = &'static str;
 
-        if let Pair(childref) = &mut self.r {
-            if let Some(child) = Rc::get_mut(childref) {
-                if 2 < depth {
-                    if let (Regular(_), Regular(_)) = (&child.l, &child.r) {
-                        should_explode = true;
+    // Every pair has to have exactly two elements, separated by one comma
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut values = Vec::new();
+        // How many elements each open pair has so far
+        let mut pairs: Vec<u32> = Vec::new();
+        let mut expect = Expect::Element;
+        let mut chars = s.trim().chars().peekable();
+        while let Some(c) = chars.next() {
+            let element_done = match (c, expect) {
+                ('[', Expect::Element) => {
+                    pairs.push(0);
+                    false
+                }
+                ('0'..='9', Expect::Element) => {
+                    let mut n = c.to_digit(10).unwrap() as u64;
+                    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                        n = n * 10 + digit as u64;
+                        chars.next();
                     }
+                    values.push((n, pairs.len() as u32));
+                    true
                 }
-
-                if !should_explode {
-                    match child.reduce(depth + 1) {
-                        Explotion(le, re) => {
-                            // Pass the left value left and the right value upwards
-                            if le != 0 {
-                                match &mut self.l {
-                                    Pair(childref) => {
-                                        pass_value_right(Rc::get_mut(childref).unwrap(), le)
-                                    }
-                                    Regular(n) => *n += re,
-                                }
-                            }
-                            return Explotion(0, re);
+                (',', Expect::Comma) => {
+                    expect = Expect::Element;
+                    false
+                }
+                (']', Expect::Close) => {
+                    pairs.pop();
+                    true
+                }
+                (']', Expect::End) => return Err("Unmatched closing bracket"),
+                ('[' | '0'..='9' | ',', Expect::End) => return Err("Trailing characters"),
+                ('[' | '0'..='9', _) => return Err("Pairs have exactly two elements"),
+                (',', Expect::Close) => return Err("Pairs have exactly two elements"),
+                (',', _) => return Err("Unexpected comma"),
+                (']', _) if !pairs.is_empty() => return Err("Pairs have exactly two elements"),
+                (']', _) => return Err("Unmatched closing bracket"),
+                _ => return Err("Unexpected character"),
+            };
+            if element_done {
+                expect = match pairs.last_mut() {
+                    None => Expect::End,
+                    Some(elements) => {
+                        *elements += 1;
+                        if *elements == 1 {
+                            Expect::Comma
+                        } else {
+                            Expect::Close
                         }
-                        Split => return Split,
-                        Nothing => (),
                     }
-                }
+                };
             }
         }
 
-        if should_explode {
-            if let Explotion(a, b) = self.explode() {
-                return Explotion(a, b);
-            }
+        match expect {
+            Expect::End => Ok(Number(values)),
+            _ if values.is_empty() && pairs.is_empty() => Err("Empty number"),
+            _ => Err("Unmatched opening bracket"),
         }
-
-        // Split:
-        self.split()
     }
+}
 
-    fn explode(&mut self) -> ReductionResult {
-        use ReductionResult::{Explotion, Nothing};
-        let mut explode_maybe = None;
-
-        if let Pair(childref) = &mut self.l {
-            if let Some(child) = Rc::get_mut(childref) {
-                if let (Regular(a), Regular(b)) = (&child.l, &child.r) {
-                    explode_maybe = Some(Explotion(*a, 0));
-                    if let Regular(n) = &mut self.r {
-                        *n += *b;
-                    }
-                }
-            }
-        }
-        if let Some(expl) = explode_maybe {
-            self.l = Regular(0);
-            return expl;
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
         }
+        self.fmt_element(f, &mut 0, 0)
+    }
+}
 
-        if let Pair(childref) = &mut self.r {
-            if let Some(child) = Rc::get_mut(childref) {
-                if let (Regular(a), Regular(b)) = (&child.l, &child.r) {
-                    explode_maybe = Some(Explotion(0, *b));
-                    if let Regu
-                    self.l = Regular(*a);
-                }
-            }
-        }
-        if let Some(expl) = explode_maybe {
-            self.r = Regular(0);
-            return expl;
+impl Add for Number {
+    type Output = Number;
+
+    fn add(self, rhs: Number) -> Number {
+        if self.0.is_empty() {
+            return rhs;
         }
+        let mut sum = Number(
+            self.0
+                .into_iter()
+                .chain(rhs.0)
+                .map(|(n, depth)| (n, depth + 1))
+                .collect(),
+        );
+        sum.reduce();
+        sum
+    }
+}
 
-        Nothing
+impl std::iter::Sum for Number {
+    fn sum<I: Iterator<Item = Number>>(iter: I) -> Number {
+        iter.fold(Number::default(), |a, b| a + b)
     }
+}
 
-    fn split(&mut self) -> ReductionResult {
-        use ReductionResult::{Nothing, Split};
-        if let Regular(n) = self.l {
-            self.l = Pair(Rc::new(Number {
-                l: Regular(Integer::div_floor(&n, &2)),
-                r: Regular(Integer::div_ceil(&n, &2)),
-            }));
-            return Split;
-        }
-        if let Regular(n) = self.r {
-            self.r = Pair(Rc::new(Number {
-                l: Regular(Integer::div_floor(&n, &2)),
-                r: Regular(Integer::div_ceil(&n, &2)),
-            }));
-            return Split;
-        }
+// Blank lines are skipped, but any other line has to be a number
+fn parse_homework(input: &str) -> Result<Vec<Number>, String> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            line.parse()
+                .map_err(|e| format!("Line {}: {} in '{}'", i + 1, e, line))
+        })
+        .collect()
+}
 
-        Nothing
+fn largest_pairwise_magnitude(numbers: &[Number]) -> u64 {
+    numbers
+        .iter()
+        .permutations(2)
+        .map(|pair| (pair[0].clone() + pair[1].clone()).magnitude())
+        .max()
+        .unwrap_or(0)
+}
+
+const HOMEWORK_EXAMPLE: &str = "[[[0,[5,8]],[[1,7],[9,6]]],[[4,[1,2]],[[1,4],2]]]
+[[[5,[2,8]],4],[5,[[9,9],0]]]
+[6,[[[6,2],[5,6]],[[7,6],[4,7]]]]
+[[[6,[0,7]],[0,9]],[4,[9,[9,0]]]]
+[[[7,[6,4]],[3,[1,3]]],[[[5,5],1],9]]
+[[6,[[7,3],[3,2]]],[[[3,8],[5,7]],4]]
+[[[[5,4],[7,7]],8],[[8,3],8]]
+[[9,3],[[9,9],[6,[4,9]]]]
+[[2,[[7,7],7]],[[5,8],[[9,3],[0,2]]]]
+[[[[5,2],5],[8,[3,7]]],[[5,[7,5]],[4,4]]]";
+
+fn main() {
+    // The puzzle input isn't checked in, so run on the example homework
+    // const INPUT: &str = include_str!("../inputs/18.txt");
+    let numbers = parse_homework(HOMEWORK_EXAMPLE).unwrap();
+
+    let sum: Number = numbers.iter().cloned().sum();
+    println!("Final sum: {}", sum);
+    println!("Magnitude of final sum: {}", sum.magnitude());
+    println!(
+        "Largest magnitude of any two numbers: {}",
+        largest_pairwise_magnitude(&numbers)
+    );
+}
+
+#[cfg(test)]
+fn num(s: &str) -> Number {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_parse_display() {
+    for s in [
+        "[1,2]",
+        "[[1,2],3]",
+        "[9,[8,7]]",
+        "[[1,9],[8,5]]",
+        "[[[[1,2],[3,4]],[[5,6],[7,8]]],9]",
+    ] {
+        assert_eq!(num(s).to_string(), s);
     }
+    assert!("[1,2".parse::<Number>().is_err());
+    assert!("[1,2]]".parse::<Number>().is_err());
+}
+
+#[test]
+fn test_parse_arity() {
+    assert_eq!(
+        "[[1]]".parse::<Number>(),
+        Err("Pairs have exactly two elements")
+    );
+    assert_eq!(
+        "[1,2,3]".parse::<Number>(),
+        Err("Pairs have exactly two elements")
+    );
+    assert_eq!("[1,,2]".parse::<Number>(), Err("Unexpected comma"));
+    assert_eq!("[,1]".parse::<Number>(), Err("Unexpected comma"));
+    assert_eq!(
+        "[]".parse::<Number>(),
+        Err("Pairs have exactly two elements")
+    );
+    assert_eq!("]".parse::<Number>(), Err("Unmatched closing bracket"));
+    assert_eq!("[1,2][3,4]".parse::<Number>(), Err("Trailing characters"));
+    assert_eq!("".parse::<Number>(), Err("Empty number"));
+    assert_eq!(
+        parse_homework("[1,2]\n\n[[1],2]"),
+        Err(String::from(
+            "Line 3: Pairs have exactly two elements in '[[1],2]'"
+        ))
+    );
 }
 
-fn pass_value_left(number: &mut Number, value: u64) {
-    match &mut number.l {
-        Regular(n) => *n += value,
-        Pair(childref) => pass_value_left(Rc::get_mut(childref).unwrap(), value)
+#[test]
+fn test_explode() {
+    let examples = [
+        ("[[[[[9,8],1],2],3],4]", "[[[[0,9],2],3],4]"),
+        ("[7,[6,[5,[4,[3,2]]]]]", "[7,[6,[5,[7,0]]]]"),
+        ("[[6,[5,[4,[3,2]]]],1]", "[[6,[5,[7,0]]],3]"),
+        (
+            "[[3,[2,[1,[7,3]]]],[6,[5,[4,[3,2]]]]]",
+            "[[3,[2,[8,0]]],[9,[5,[4,[3,2]]]]]",
+        ),
+        (
+            "[[3,[2,[8,0]]],[9,[5,[4,[3,2]]]]]",
+            "[[3,[2,[8,0]]],[9,[5,[7,0]]]]",
+        ),
+    ];
+    for (before, after) in examples {
+        let mut n = num(before);
+        assert!(n.explode());
+        assert_eq!(n.to_string(), after);
     }
 }
 
-fn pass_value_right(number: &mut Number, value: u64) {
-    match &mut number.r {
-        Regular(n) => *n += value,
-        Pair(childref) => pass_value_right(Rc::get_mut(childref).unwrap(), value)
+#[test]
+fn test_split() {
+    let mut n = num("[[[[0,7],4],[15,[0,13]]],[1,1]]");
+    assert!(n.split());
+    assert_eq!(n.to_string(), "[[[[0,7],4],[[7,8],[0,13]]],[1,1]]");
+    assert!(n.split());
+    assert_eq!(n.to_string(), "[[[[0,7],4],[[7,8],[0,[6,7]]]],[1,1]]");
+    assert!(!num("[[1,9],[8,5]]").split());
+}
+
+#[test]
+fn test_add() {
+    assert_eq!(
+        num("[[[[4,3],4],4],[7,[[8,4],9]]]") + num("[1,1]"),
+        num("[[[[0,7],4],[[7,8],[6,0]]],[8,1]]")
+    );
+}
+
+#[test]
+fn test_sum() {
+    let numbers = parse_homework("[1,1]\n[2,2]\n[3,3]\n[4,4]\n[5,5]\n[6,6]").unwrap();
+    let sum: Number = numbers[..4].iter().cloned().sum();
+    assert_eq!(sum.to_string(), "[[[[1,1],[2,2]],[3,3]],[4,4]]");
+    let sum: Number = numbers[..5].iter().cloned().sum();
+    assert_eq!(sum.to_string(), "[[[[3,0],[5,3]],[4,4]],[5,5]]");
+    let sum: Number = numbers.into_iter().sum();
+    assert_eq!(sum.to_string(), "[[[[5,0],[7,4]],[5,5]],[6,6]]");
+}
+
+#[test]
+fn test_larger_sum() {
+    const INPUT: &str = "[[[0,[4,5]],[0,0]],[[[4,5],[2,6]],[9,5]]]\n[7,[[[3,7],[4,3]],[[6,3],[8,8]]]]\n[[2,[[0,8],[3,4]]],[[[6,7],1],[7,[1,6]]]]\n[[[[2,4],7],[6,[0,5]]],[[[6,8],[2,8]],[[2,1],[4,5]]]]\n[7,[5,[[3,8],[1,4]]]]\n[[2,[2,2]],[8,[8,1]]]\n[2,9]\n[1,[[[9,3],9],[[9,0],[0,7]]]]\n[[[5,[7,4]],7],1]\n[[[[4,2],2],6],[8,7]]";
+    let sum: Number = parse_homework(INPUT).unwrap().into_iter().sum();
+    assert_eq!(
+        sum.to_string(),
+        "[[[[8,7],[7,7]],[[8,6],[7,7]]],[[[0,7],[6,6]],[8,7]]]"
+    );
+}
+
+#[test]
+fn test_magnitude() {
+    let examples = [
+        ("[[1,2],[[3,4],5]]", 143),
+        ("[[[[0,7],4],[[7,8],[6,0]]],[8,1]]", 1384),
+        ("[[[[1,1],[2,2]],[3,3]],[4,4]]", 445),
+        ("[[[[3,0],[5,3]],[4,4]],[5,5]]", 791),
+        ("[[[[5,0],[7,4]],[5,5]],[6,6]]", 1137),
+        (
+            "[[[[8,7],[7,7]],[[8,6],[7,7]]],[[[0,7],[6,6]],[8,7]]]",
+            3488,
+        ),
+    ];
+    for (s, magnitude) in examples {
+        assert_eq!(num(s).magnitude(), magnitude);
     }
 }
 
-fn main() {
-    // [[[[[9,8],1],2],3],4]
-    let mut num = Number {
-        l: Pair(Rc::new(Number {
-            l: Pair(Rc::new(Number {
-                l: Pair(Rc::new(Number {
-                    l: Pair(Rc::new(Number {
-                        l: Regular(9),
-                        r: Regular(8),
-                    })),
-                    r: Regular(1),
-                })),
-                r: Regular(2),
-            })),
-            r: Regular(3),
-        })),
-        r: Regular(4),
-    };
-
-    println!("Number reduction returned {:?}, and it is now: {:?}", num.reduce(0), num);
+#[test]
+fn test_homework() {
+    let numbers = parse_homework(HOMEWORK_EXAMPLE).unwrap();
+    let sum: Number = numbers.iter().cloned().sum();
+    assert_eq!(
+        sum.to_string(),
+        "[[[[6,6],[7,6]],[[7,7],[7,0]]],[[[7,7],[7,7]],[[7,8],[9,9]]]]"
+    );
+    assert_eq!(sum.magnitude(), 4140);
+    assert_eq!(largest_pairwise_magnitude(&numbers), 3993);
 }
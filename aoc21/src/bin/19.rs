@@ -1,60 +1,195 @@
-use ndarray::{arr1, Array1};
-use std::collections::HashSet;
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet, VecDeque};
 
-type Vector = Array1<i64>;
+type Vector = [i64; 3];
+type Matrix = [[i64; 3]; 3];
 
 fn main() {
     /*
      * 1. Use first scanner as coordinate basis
-     * 2. Make triplets of three points and try to find a
-     * common triangle between scanners coordinate systems
-     * (vectors are not bound by coordinate space in affine space)
-     * 3. If a common triangle has been identified, find transformation
-     * from one triangle to the other and transform coordinate space
-     * of other scanners to scanner 0.
+     * 2. Compare the pairwise beacon distances of two scanners, which don't
+     * depend on orientation, to skip pairs that can't share 12 beacons
+     * 3. For the remaining pairs, try every rotation and look for an offset
+     * that lines up at least 12 beacons, then transform the other scanner
+     * into scanner 0's coordinate space and continue from there.
      */
+    const INPUT: &str = include_str!("../inputs/19.txt");
+    let map = assemble_map(&parse_input(INPUT)).expect("Not all scanners overlap");
 
-    println!("Input: {:?}", parse_input(EXAMPLE_INPUT));
+    println!("Number of beacons: {}", map.beacons.len());
+    println!(
+        "Largest distance between two scanners: {}",
+        map.max_scanner_distance()
+    );
 }
 
 fn parse_input(input: &str) -> Vec<Vec<Vector>> {
-    input.split("\n\n").map(|s|s.lines().skip(1).map(|l|{
-        let nums: Vec<i64> = l.splitn(3, ',').map(|n|n.parse().unwrap()).collect();
-        arr1(&[nums[0], nums[1], nums[2]])
-    }).collect()).collect()
-}
-
-fn find_all_vector_mutations(beacons: &Vec<Vector>) -> HashSet<Vector> {
-    let mut uniques = HashSet::new();
-    for i in 0..beacons.len() {
-        for j in 0..beacons.len() {
-            if i != j {
-                let a = beacons.get(i).unwrap();
-                let b = beacons.get(j).unwrap();
-                
-                if !uniques.contains(&(a - b)) && !uniques.contains(&(b - a)) {
-                    uniques.insert(a-b);
-                }
+    input
+        .split("\n\n")
+        .map(|s| {
+            s.lines()
+                .skip(1)
+                .filter_map(|l| {
+                    l.splitn(3, ',')
+                        .map(|n| n.trim().parse().unwrap())
+                        .collect_tuple()
+                        .map(|(x, y, z)| [x, y, z])
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn determinant(m: &Matrix) -> i64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+// The 24 proper rotations are the signed permutation matrices without a flip
+fn rotations() -> Vec<Matrix> {
+    (0..3)
+        .permutations(3)
+        .cartesian_product(0..8)
+        .map(|(axes, signs)| {
+            let mut m = [[0; 3]; 3];
+            for (row, &axis) in axes.iter().enumerate() {
+                m[row][axis] = if signs & (1 << row) == 0 { 1 } else { -1 };
             }
-        }
+            m
+        })
+        .filter(|m| determinant(m) == 1)
+        .collect()
+}
+
+fn rotate(m: &Matrix, v: &Vector) -> Vector {
+    [0, 1, 2].map(|row| (0..3).map(|i| m[row][i] * v[i]).sum())
+}
+
+fn sub(a: &Vector, b: &Vector) -> Vector {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn add(a: &Vector, b: &Vector) -> Vector {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn manhattan(a: &Vector, b: &Vector) -> i64 {
+    sub(a, b).iter().map(|n| n.abs()).sum()
+}
+
+const REQUIRED_OVERLAP: usize = 12;
+
+// Squared distances between every pair of beacons, which are the same no
+// matter how the scanner is rotated or where it is.
+fn fingerprint(beacons: &[Vector]) -> HashMap<i64, usize> {
+    beacons
+        .iter()
+        .tuple_combinations()
+        .map(|(a, b)| sub(a, b).iter().map(|n| n * n).sum())
+        .counts()
+}
+
+fn may_overlap(a: &HashMap<i64, usize>, b: &HashMap<i64, usize>) -> bool {
+    let common: usize = a
+        .iter()
+        .map(|(d, count)| *count.min(b.get(d).unwrap_or(&0)))
+        .sum();
+    common >= REQUIRED_OVERLAP * (REQUIRED_OVERLAP - 1) / 2
+}
+
+// Finds the rotation and offset that maps enough of other's beacons onto known
+fn align(known: &[Vector], other: &[Vector], rotations: &[Matrix]) -> Option<(Matrix, Vector)> {
+    rotations.iter().find_map(|m| {
+        let rotated = other.iter().map(|v| rotate(m, v)).collect::<Vec<Vector>>();
+        known
+            .iter()
+            .cartesian_product(rotated.iter())
+            .map(|(k, r)| sub(k, r))
+            .counts()
+            .into_iter()
+            .find(|(_, count)| REQUIRED_OVERLAP <= *count)
+            .map(|(offset, _)| (*m, offset))
+    })
+}
+
+struct BeaconMap {
+    beacons: HashSet<Vector>,
+    scanners: Vec<Vector>,
+}
+
+impl BeaconMap {
+    fn max_scanner_distance(&self) -> i64 {
+        self.scanners
+            .iter()
+            .tuple_combinations()
+            .map(|(a, b)| manhattan(a, b))
+            .max()
+            .unwrap_or(0)
     }
-    uniques
-}
-
-fn find_common_triangle(beacons0: &Vec<Vector>, beacons1: &Vec<Vector>) -> Option<[Vector; 3> {
-    let common_vectors: Vec<Vector> = find_all_vector_mutations(beacons0).union(&find_all_vector_mutations(beacons1)).collect();
-    if 2 < common_vectors.len() {
-        Some([
-            common_vectors.iter().nth(0),
-            common_vectors.iter().nth(1),
-            common_vectors.iter().nth(2)
-        ])
-    } else {
-        None
+}
+
+fn assemble_map(scanners: &[Vec<Vector>]) -> Option<BeaconMap> {
+    let rotations = rotations();
+    let fingerprints = scanners.iter().map(|s| fingerprint(s)).collect::<Vec<_>>();
+
+    let mut aligned: Vec<Option<(Vector, Vec<Vector>)>> = vec![None; scanners.len()];
+    aligned[0] = Some(([0; 3], scanners.first()?.clone()));
+    let mut queue = VecDeque::from([0]);
+    while let Some(i) = queue.pop_front() {
+        let known = aligned[i].as_ref().unwrap().1.clone();
+        for j in 0..scanners.len() {
+            if aligned[j].is_some() || !may_overlap(&fingerprints[i], &fingerprints[j]) {
+                continue;
+            }
+            if let Some((m, offset)) = align(&known, &scanners[j], &rotations) {
+                let beacons = scanners[j]
+                    .iter()
+                    .map(|v| add(&rotate(&m, v), &offset))
+                    .collect();
+                aligned[j] = Some((offset, beacons));
+                queue.push_back(j);
+            }
+        }
     }
+
+    let aligned = aligned.into_iter().collect::<Option<Vec<_>>>()?;
+    Some(BeaconMap {
+        scanners: aligned.iter().map(|(position, _)| *position).collect(),
+        beacons: aligned
+            .into_iter()
+            .flat_map(|(_, beacons)| beacons)
+            .collect(),
+    })
+}
+
+#[test]
+fn test_rotations() {
+    let rotations = rotations();
+    assert_eq!(rotations.len(), 24);
+    let v = [1, 2, 3];
+    assert_eq!(rotations.iter().map(|m| rotate(m, &v)).unique().count(), 24);
+}
+
+#[test]
+fn test_example() {
+    let map = assemble_map(&parse_input(EXAMPLE_INPUT)).unwrap();
+    assert_eq!(map.beacons.len(), 79);
+    assert_eq!(
+        map.scanners,
+        vec![
+            [0, 0, 0],
+            [68, -1246, -43],
+            [1105, -1205, 1229],
+            [-92, -2380, -20],
+            [-20, -1133, 1061]
+        ]
+    );
+    assert_eq!(map.max_scanner_distance(), 3621);
 }
 
-let EXAMPLE_INPUT: &str = "--- scanner 0 ---
+#[cfg(test)]
+const EXAMPLE_INPUT: &str = "--- scanner 0 ---
 404,-588,-901
 528,-643,409
 -838,591,734
@@ -189,4 +324,4 @@ let EXAMPLE_INPUT: &str = "--- scanner 0 ---
 839,-516,451
 891,-625,532
 -652,-548,-490
-30,-46,-14";
\ No newline at end of file
+30,-46,-14";
@@ -1,39 +1,331 @@
+use std::fmt;
+
 fn main() {
     const INPUT: &str = include_str!("../inputs/2.txt");
-    let (x, y) = INPUT
-        .lines()
-        .map(cmd_to_coord)
-        .fold((0, 0), |l, r| (l.0 + r.0, l.1 + r.1));
-    println!(
-        "Part 1: Final horizontal position multiplication: {}",
-        x * y
-    );
+    let commands = match parse_commands(INPUT) {
+        Ok(commands) => commands,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let args: Vec<String> = std::env::args().collect();
+    // Prints the position after every command as well
+    let trace = args.iter().any(|a| a == "--trace");
 
-    let (x, y, ..) = INPUT
+    let mut parts: Vec<(&str, &dyn MovementModel)> = vec![("Part 1", &Direct), ("Part 2", &Aim)];
+    if args.iter().any(|a| a == "--3d") {
+        parts.push(("3D", &Aim3d));
+    }
+    for (part, model) in parts {
+        let mut submarine = Submarine::default();
+        match submarine.run(model, &commands) {
+            Ok(positions) => {
+                if trace {
+                    for ((line, command), position) in commands.iter().zip(&positions) {
+                        println!("{:>5}: {:<12} {}", line, command.to_string(), position);
+                    }
+                }
+                println!(
+                    "{}: Final horizontal position multiplication: {}",
+                    part,
+                    submarine.position.horizontal * submarine.position.depth
+                );
+            }
+            Err(e) => eprintln!("{}: {}", part, e),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Command {
+    Forward(i64),
+    Down(i64),
+    Up(i64),
+    Left(i64),
+    Right(i64),
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Forward(n) => write!(f, "forward {}", n),
+            Command::Down(n) => write!(f, "down {}", n),
+            Command::Up(n) => write!(f, "up {}", n),
+            Command::Left(n) => write!(f, "left {}", n),
+            Command::Right(n) => write!(f, "right {}", n),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Error {
+    UnknownCommand { line: usize, command: String },
+    BadAmount { line: usize, amount: String },
+    Malformed { line: usize, text: String },
+    // A command the movement model has no way of carrying out
+    Unsupported { line: usize, command: Command },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownCommand { line, command } => {
+                write!(f, "Line {}: unknown command '{}'", line, command)
+            }
+            Error::BadAmount { line, amount } => {
+                write!(f, "Line {}: '{}' is not a valid amount", line, amount)
+            }
+            Error::Malformed { line, text } => {
+                write!(
+                    f,
+                    "Line {}: expected '<command> <amount>', got '{}'",
+                    line, text
+                )
+            }
+            Error::Unsupported { line, command } => {
+                write!(f, "Line {}: '{}' is not supported here", line, command)
+            }
+        }
+    }
+}
+
+// Commands along with the (1-based) line they're on. Blank lines are skipped.
+fn parse_commands(input: &str) -> Result<Vec<(usize, Command)>, Error> {
+    input
         .lines()
-        .map(|s| {
-            let (x, y) = cmd_to_coord(s);
-            (x, y, 0)
+        .enumerate()
+        .filter(|(_, s)| !s.trim().is_empty())
+        .map(|(i, s)| {
+            let line = i + 1;
+            let (command, amount) = match s.split_whitespace().collect::<Vec<&str>>()[..] {
+                [command, amount] => (command, amount),
+                _ => {
+                    return Err(Error::Malformed {
+                        line,
+                        text: s.to_string(),
+                    })
+                }
+            };
+            let n: i64 = amount.parse().map_err(|_| Error::BadAmount {
+                line,
+                amount: amount.to_string(),
+            })?;
+            let command = match command {
+                "forward" => Command::Forward(n),
+                "down" => Command::Down(n),
+                "up" => Command::Up(n),
+                "left" => Command::Left(n),
+                "right" => Command::Right(n),
+                _ => {
+                    return Err(Error::UnknownCommand {
+                        line,
+                        command: command.to_string(),
+                    })
+                }
+            };
+            Ok((line, command))
         })
-        .fold((0, 0, 0), |l, r| (l.0 + r.0, l.1 + l.2 * r.0, l.2 + r.1));
-    println!(
-        "Part 2: Final horizontal position multiplication: {}",
-        x * y
-    );
-}
-
-fn cmd_to_coord(cmd: &str) -> (i32, i32) {
-    let arr: Vec<&str> = cmd.split_whitespace().collect();
-    match arr[..] {
-        [dir, amount] => {
-            let num = amount.parse::<i32>().unwrap();
-            match dir {
-                "forward" => (num, 0),
-                "down" => (0, num),
-                "up" => (0, -num),
-                _ => (0, 0),
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Position {
+    horizontal: i64,
+    depth: i64,
+    // Sideways, with right being positive
+    lateral: i64,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "horizontal {}, depth {}, lateral {}",
+            self.horizontal, self.depth, self.lateral
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct Submarine {
+    position: Position,
+    // Pitch, where positive is pointing down
+    aim: i64,
+    // Yaw, where positive is pointing right
+    heading: i64,
+}
+
+impl Submarine {
+    // Runs every command, returning the position after each of them
+    fn run(
+        &mut self,
+        model: &dyn MovementModel,
+        commands: &[(usize, Command)],
+    ) -> Result<Vec<Position>, Error> {
+        commands
+            .iter()
+            .map(|&(line, command)| {
+                if !model.supports(&command) {
+                    return Err(Error::Unsupported { line, command });
+                }
+                model.apply(self, command);
+                Ok(self.position)
+            })
+            .collect()
+    }
+}
+
+trait MovementModel {
+    fn apply(&self, submarine: &mut Submarine, command: Command);
+
+    fn supports(&self, _command: &Command) -> bool {
+        true
+    }
+}
+
+// Down and up change the depth directly
+struct Direct;
+
+impl MovementModel for Direct {
+    fn apply(&self, submarine: &mut Submarine, command: Command) {
+        let p = &mut submarine.position;
+        match command {
+            Command::Forward(n) => p.horizontal += n,
+            Command::Down(n) => p.depth += n,
+            Command::Up(n) => p.depth -= n,
+            Command::Left(_) | Command::Right(_) => (),
+        }
+    }
+
+    fn supports(&self, command: &Command) -> bool {
+        !matches!(command, Command::Left(_) | Command::Right(_))
+    }
+}
+
+// Down and up tilt the submarine, and moving forward dives along the tilt
+struct Aim;
+
+impl MovementModel for Aim {
+    fn apply(&self, submarine: &mut Submarine, command: Command) {
+        match command {
+            Command::Forward(n) => {
+                submarine.position.horizontal += n;
+                submarine.position.depth += submarine.aim * n;
             }
+            Command::Down(n) => submarine.aim += n,
+            Command::Up(n) => submarine.aim -= n,
+            Command::Left(_) | Command::Right(_) => (),
         }
-        _ => (0, 0),
     }
+
+    fn supports(&self, command: &Command) -> bool {
+        Direct.supports(command)
+    }
+}
+
+// Aim in both directions, with left and right turning the submarine the same
+// way down and up tilt it
+struct Aim3d;
+
+impl MovementModel for Aim3d {
+    fn apply(&self, submarine: &mut Submarine, command: Command) {
+        match command {
+            Command::Forward(n) => {
+                submarine.position.horizontal += n;
+                submarine.position.depth += submarine.aim * n;
+                submarine.position.lateral += submarine.heading * n;
+            }
+            Command::Down(n) => submarine.aim += n,
+            Command::Up(n) => submarine.aim -= n,
+            Command::Left(n) => submarine.heading -= n,
+            Command::Right(n) => submarine.heading += n,
+        }
+    }
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = "forward 5\ndown 5\nforward 8\nup 3\ndown 8\nforward 2";
+
+#[test]
+fn test_example() {
+    let commands = parse_commands(EXAMPLE).unwrap();
+    let mut submarine = Submarine::default();
+    submarine.run(&Direct, &commands).unwrap();
+    assert_eq!(
+        submarine.position.horizontal * submarine.position.depth,
+        150
+    );
+
+    let mut submarine = Submarine::default();
+    let trace = submarine.run(&Aim, &commands).unwrap();
+    assert_eq!(
+        trace
+            .iter()
+            .map(|p| (p.horizontal, p.depth))
+            .collect::<Vec<_>>(),
+        vec![(5, 0), (5, 0), (13, 40), (13, 40), (13, 40), (15, 60)]
+    );
+    assert_eq!(submarine.aim, 10);
+    assert_eq!(
+        submarine.position.horizontal * submarine.position.depth,
+        900
+    );
+}
+
+#[test]
+fn test_strict_parsing() {
+    assert_eq!(
+        parse_commands("forward 5\n\nbackward 2"),
+        Err(Error::UnknownCommand {
+            line: 3,
+            command: "backward".to_string()
+        })
+    );
+    assert_eq!(
+        parse_commands("down x"),
+        Err(Error::BadAmount {
+            line: 1,
+            amount: "x".to_string()
+        })
+    );
+    assert_eq!(
+        parse_commands("up 1\nup"),
+        Err(Error::Malformed {
+            line: 2,
+            text: "up".to_string()
+        })
+    );
+    assert_eq!(
+        Error::UnknownCommand {
+            line: 3,
+            command: "backward".to_string()
+        }
+        .to_string(),
+        "Line 3: unknown command 'backward'"
+    );
+}
+
+#[test]
+fn test_3d() {
+    let commands = parse_commands("down 2\nright 3\nforward 4\nleft 5\nforward 1").unwrap();
+    let mut submarine = Submarine::default();
+    let trace = submarine.run(&Aim3d, &commands).unwrap();
+    assert_eq!(
+        trace.last(),
+        Some(&Position {
+            horizontal: 5,
+            depth: 10,
+            lateral: 10
+        })
+    );
+
+    // The flat models can't turn
+    assert_eq!(
+        Submarine::default().run(&Aim, &commands),
+        Err(Error::Unsupported {
+            line: 2,
+            command: Command::Right(3)
+        })
+    );
 }
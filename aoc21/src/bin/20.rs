@@ -1,148 +1,163 @@
-use std::iter;
+use common::pbm;
+use std::fmt;
+use std::path::Path;
 
 fn main() {
-//     const INPUT: &str = "..#.#..#####.#.#.#.###.##.....###.##.#..###.####..#####..#....#..#..##..##
-// #..######.###...####..#..#####..##..#.#####...##.#.#..#.##..#.#......#.###
-// .######.###.####...#.##.##..#..#..#####.....#.#....###..#.##......#.....#.
-// .#..#..##..#...##.######.####.####.#.#...#.......#..#.#.#...####.##.#.....
-// .#..#...##.#.##..#...##.#.##..###.#......#.#.......#.#.#.####.###.##...#..
-// ...####.#..#..#.##.#....##..#.####....##...##..#...#......#.#.......#.....
-// ..##..####..#...#.#.#...##..#.#..###..#####........#..####......#..#
-
-// #..#.
-// #....
-// ##..#
-// ..#..
-// ..###";
     const INPUT: &str = include_str!("../inputs/20.txt");
+    let (rule, image) = parse_input(INPUT);
+
+    let enhanced = enhance_n(&rule, &image, 2);
+    println!(
+        "Lit pixels after 2 enhancements: {}",
+        enhanced.lit_count().unwrap()
+    );
+    let enhanced = enhance_n(&rule, &image, 50);
+    println!(
+        "Lit pixels after 50 enhancements: {}",
+        enhanced.lit_count().unwrap()
+    );
+
+    // Optionally dump every step as a PBM image to the given directory
+    if let Some(dir) = std::env::args().nth(1) {
+        let steps = std::iter::successors(Some(image), |image| Some(image.enhance(&rule)));
+        pbm::export(Path::new(&dir), "20", steps.take(51).map(|i| i.to_pbm())).unwrap();
+    }
+}
 
-    let (image_enhance_pattern, image) = INPUT.split_once("\n\n").unwrap();
-    let image_enhance_pattern = &image_enhance_pattern.lines().fold(String::new(), |l,r|l + &r.to_string());
-    let image = image.lines().map(|s|s.to_string()).collect::<Vec<String>>();
-    println!("Enhancement pattern: {}", image_enhance_pattern);
-
-    println!("Image: \n{}", image.join("\n"));
-
-    let enhanced = enhance_n(&image_enhance_pattern, &image, 50);
-    println!("Image enhanced: \n{}", enhanced.join("\n"));
-    println!("Which is {} lit pixels", enhanced.join("\n").chars().map(|c|if c == '#' { 1 } else { 0 }).sum::<u64>());
+const WORD_BITS: usize = u64::BITS as usize;
+
+// A finite window of pixels packed into 64-bit words, surrounded by an
+// infinite background that is all lit or all dark.
+#[derive(Clone, PartialEq, Debug)]
+struct Image {
+    width: usize,
+    height: usize,
+    words_per_row: usize,
+    bits: Vec<u64>,
+    background: bool,
 }
 
-fn enhance_n(enhance_pattern: &str, image: &Vec<String>, n: u32) -> Vec<String> {
-    let mut enhanced_image = image_expand(&image, '.');
-    for i in 0..n {
-        let replacement_token = get_infinite_pixel(&enhance_pattern, i);
-        enhanced_image = image_expand(&image_expand(&enhanced_image, replacement_token), replacement_token);
-        enhanced_image = enhance(&enhance_pattern, &enhanced_image);
-        enhanced_image = image_reduce(&enhanced_image);
+impl Image {
+    fn new(width: usize, height: usize, background: bool) -> Image {
+        let words_per_row = width.div_ceil(WORD_BITS);
+        Image {
+            width,
+            height,
+            words_per_row,
+            bits: vec![0; words_per_row * height],
+            background,
+        }
     }
-    enhanced_image
-}
 
-fn image_expand(image: &Vec<String>, replacement_token: char) -> Vec<String> {
-    let image_width = image.iter().next().unwrap().len();
-    let mut new_image = Vec::with_capacity(image.len()+2);
-    new_image.push(iter::repeat(replacement_token).take(image_width+2).collect::<String>());
-    for line in image {
-        let mut new_line = String::with_capacity(image_width + 2);
-        new_line.push(replacement_token);
-        new_line += line;
-        new_line.push(replacement_token);
-        new_image.push(new_line);
+    fn parse(input: &str) -> Image {
+        let lines = input.lines().collect::<Vec<&str>>();
+        let width = lines.first().map(|l| l.len()).unwrap_or(0);
+        let mut image = Image::new(width, lines.len(), false);
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                image.set(x, y, c == '#');
+            }
+        }
+        image
     }
-    new_image.push(iter::repeat(replacement_token).take(image_width+2).collect::<String>());
-    new_image
-}
 
-fn image_reduce(image: &Vec<String>) -> Vec<String> {
-    let mut new_image = Vec::with_capacity(image.len() - 2);
-    let image_width = image.iter().next().unwrap().len();
-    for line in image.iter().take(image.len() - 1).skip(1) {
-        new_image.push(line.chars().take(image_width - 1).skip(1).collect());
+    fn set(&mut self, x: usize, y: usize, lit: bool) {
+        let (word, bit) = (y * self.words_per_row + x / WORD_BITS, x % WORD_BITS);
+        if lit {
+            self.bits[word] |= 1 << bit;
+        } else {
+            self.bits[word] &= !(1 << bit);
+        }
     }
-    new_image
-}
 
-fn get_infinite_pixel(enhance_pattern: &str, step: u32) -> char {
-    let mut infinite_space = '0';
-    for _ in 0..step {
-        let pat: String = [infinite_space; 9].into_iter().collect();
-        let index = usize::from_str_radix(&pat[..], 2).unwrap();
-        infinite_space = if enhance_pattern.chars().nth(index).unwrap() == '#' { '1' } else { '0' };
+    fn get(&self, x: i64, y: i64) -> bool {
+        if x < 0 || y < 0 || self.width as i64 <= x || self.height as i64 <= y {
+            return self.background;
+        }
+        let (x, y) = (x as usize, y as usize);
+        self.bits[y * self.words_per_row + x / WORD_BITS] >> (x % WORD_BITS) & 1 == 1
     }
-    if infinite_space == '1' { '#' } else { '.' }
-}
 
-fn enhance(pattern: &str, image: &Vec<String>) -> Vec<String> {
-    let image_width = image.iter().next().unwrap().len();
-    let mut enhanced_image: Vec<Vec<char>> = image.iter().map(|s|s.chars().collect()).collect();
-    for (y, line) in enhanced_image.iter_mut().enumerate() {
-        if y == 0 || image.len()-1 == y {
-            continue;
+    // None if the background is lit, as there are infinitely many lit pixels
+    fn lit_count(&self) -> Option<usize> {
+        if self.background {
+            None
+        } else {
+            Some(self.bits.iter().map(|w| w.count_ones() as usize).sum())
         }
-        for (x, pixel) in line.iter_mut().enumerate() {
-            if x == 0 || image_width-1 == x {
-                continue;
-            }
-            
-            let indices = to_kernel_indices((x,y));
-            let binary_pattern = indices.iter().map(|coord|{
-                let (i, j) = coord.unwrap();
-                let c = image.get(j).unwrap().chars().nth(i).unwrap();
-                match c {
-                    '#' => '1',
-                    _ => '0'
-                }
-            }).collect::<String>();
+    }
 
-            let replacement = pattern.chars().nth(usize::from_str_radix(&binary_pattern[..], 2).unwrap()).unwrap();
-            *pixel = replacement;
+    // Every enhancement grows the image by one pixel in each direction, as
+    // that's as far as the stored pixels can influence the background.
+    fn enhance(&self, rule: &[bool; 512]) -> Image {
+        let mut out = Image::new(
+            self.width + 2,
+            self.height + 2,
+            rule[self.background_index()],
+        );
+        for oy in 0..out.height {
+            let y = oy as i64 - 1;
+            // The left and middle columns of the first window are background
+            let mut index = if self.background { 0b011_011_011 } else { 0 };
+            for ox in 0..out.width {
+                let x = ox as i64 - 1;
+                index = (index << 1) & 0b110_110_110
+                    | (self.get(x + 1, y - 1) as usize) << 6
+                    | (self.get(x + 1, y) as usize) << 3
+                    | self.get(x + 1, y + 1) as usize;
+                if rule[index] {
+                    out.set(ox, oy, true);
+                }
+            }
         }
+        out
     }
-    enhanced_image.iter().map(|v|v.iter().collect()).collect()
-}
 
-fn to_kernel_indices((i, j): (usize, usize)) -> [Option<(usize, usize)>; 9] {
-    let mut out = [None; 9];
-    let mut k = 0;
-    for y in -1..2 {
-        for x in -1..2 {
-            out[k] = combine_option(safe_add(i, x), safe_add(j, y));
-            k += 1;
+    fn background_index(&self) -> usize {
+        if self.background {
+            0b111_111_111
+        } else {
+            0
         }
     }
-    out
+
+    // Lit pixels are drawn black
+    fn to_pbm(&self) -> String {
+        pbm::to_pbm(self.width, self.height, |(x, y)| {
+            self.get(x as i64, y as i64)
+        })
+    }
 }
 
-fn combine_option<T>(a: Option<T>, b: Option<T>) -> Option<(T, T)> {
-    Some((a?, b?))
+impl fmt::Display for Image {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for y in 0..self.height as i64 {
+            if y != 0 {
+                writeln!(f)?;
+            }
+            for x in 0..self.width as i64 {
+                write!(f, "{}", if self.get(x, y) { '#' } else { '.' })?;
+            }
+        }
+        Ok(())
+    }
 }
 
-fn safe_add(a: usize, b: i64) -> Option<usize> {
-    usize::try_from(i64::try_from(a).ok()? + b).ok()
+fn parse_input(input: &str) -> ([bool; 512], Image) {
+    let (rule, image) = input.split_once("\n\n").unwrap();
+    let mut pattern = [false; 512];
+    for (i, c) in rule.chars().filter(|c| !c.is_whitespace()).enumerate() {
+        pattern[i] = c == '#';
+    }
+    (pattern, Image::parse(image))
 }
 
-#[test]
-fn test_expand() {
-    const INPUT: &str = "#..#.
-#....
-##..#
-..#..
-..###";
-    let image = INPUT.lines().map(|s|s.to_string()).collect::<Vec<String>>();
-
-    assert_eq!(&image_expand(&image, '.').join("\n")[..], ".......
-.#..#..
-.#.....
-.##..#.
-...#...
-...###.
-.......");
+fn enhance_n(rule: &[bool; 512], image: &Image, n: u32) -> Image {
+    (0..n).fold(image.clone(), |image, _| image.enhance(rule))
 }
 
-#[test]
-fn test_enhance() {
-    const INPUT: &str = "..#.#..#####.#.#.#.###.##.....###.##.#..###.####..#####..#....#..#..##..##
+#[cfg(test)]
+const EXAMPLE: &str = "..#.#..#####.#.#.#.###.##.....###.##.#..###.####..#####..#....#..#..##..##
 #..######.###...####..#..#####..##..#.#####...##.#.#..#.##..#.#......#.###
 .######.###.####...#.##.##..#..#..#####.....#.#....###..#.##......#.....#.
 .#..#..##..#...##.######.####.####.#.#...#.......#..#.#.#...####.##.#.....
@@ -156,32 +171,54 @@ fn test_enhance() {
 ..#..
 ..###";
 
-    let (image_enhance_pattern, image) = INPUT.split_once("\n\n").unwrap();
-    let image_enhance_pattern = &image_enhance_pattern.lines().fold(String::new(), |l,r|l + &r.to_string());
-    let image = image.lines().map(|s|s.to_string()).collect::<Vec<String>>();
-
-    assert_eq!(&enhance_n(&image_enhance_pattern, &image, 2).join("\n")[..], "...........
-........#..
-..#..#.#...
-.#.#...###.
-.#...##.#..
-.#.....#.#.
-..#.#####..
-...#.#####.
-....##.##..
-.....###...
-...........");
+#[test]
+fn test_parse() {
+    let (_, image) = parse_input(EXAMPLE);
+    assert_eq!(image.to_string(), "#..#.\n#....\n##..#\n..#..\n..###");
+    assert_eq!(image.lit_count(), Some(10));
+    assert!(!image.get(-1, 0));
+    assert!(image.get(4, 4));
+}
+
+#[test]
+fn test_enhance() {
+    let (rule, image) = parse_input(EXAMPLE);
+
+    assert_eq!(
+        &enhance_n(&rule, &image, 2).to_string(),
+        ".......#.
+.#..#.#..
+#.#...###
+#...##.#.
+#.....#.#
+.#.#####.
+..#.#####
+...##.##.
+....###.."
+    );
+    assert_eq!(enhance_n(&rule, &image, 2).lit_count(), Some(35));
+    assert_eq!(enhance_n(&rule, &image, 50).lit_count(), Some(3351));
+}
+
+#[test]
+fn test_wide_image() {
+    // Wider than a single word, to check pixels across word boundaries
+    let (rule, _) = parse_input(EXAMPLE);
+    let image = Image::parse(&format!("{}#\n{}.", "#.".repeat(40), ".#".repeat(40)));
+    assert_eq!(image.lit_count(), Some(81));
+    let enhanced = enhance_n(&rule, &image, 2);
+    assert_eq!(enhanced.width, 85);
+    assert_eq!(Image::parse(&enhanced.to_string()), enhanced);
 }
 
 #[test]
 fn test_reddit_data() {
     // Using data from another kind redditor:
     const INPUT: &str = include_str!("../inputs/20-b.txt");
+    let (rule, image) = parse_input(INPUT);
 
-    let (image_enhance_pattern, image) = INPUT.split_once("\n\n").unwrap();
-    let image_enhance_pattern = &image_enhance_pattern.lines().fold(String::new(), |l,r|l + &r.to_string());
-    let image = image.lines().map(|s|s.to_string()).collect::<Vec<String>>();
-
-    let enhanced = enhance_n(&image_enhance_pattern, &image, 2);
-    assert_eq!(enhanced.join("").chars().map(|c|if c == '#' { 1 } else { 0 }).sum::<u64>(), 5326);
-}
\ No newline at end of file
+    let enhanced = enhance_n(&rule, &image, 2);
+    assert!(!enhanced.background);
+    assert_eq!(enhanced.lit_count(), Some(5326));
+    assert!(enhance_n(&rule, &image, 1).lit_count().is_none());
+}
@@ -1,222 +1,482 @@
 use itertools::Itertools;
 use std::collections::HashMap;
+use std::ops::{AddAssign, Mul};
 
 trait Die {
     fn roll(&mut self) -> u32;
 }
 
 struct DeterministicDie {
+    faces: u32,
     counter: u32,
 }
 
 impl DeterministicDie {
-    fn new() -> DeterministicDie {
-        DeterministicDie { counter: 1 }
+    fn new(faces: u32) -> DeterministicDie {
+        DeterministicDie { faces, counter: 1 }
     }
 }
 
 impl Die for DeterministicDie {
     fn roll(&mut self) -> u32 {
-        let ret = (self.counter - 1) % 100 + 1;
+        let ret = (self.counter - 1) % self.faces + 1;
         self.counter += 1;
         ret
     }
 }
 
-struct DiracDie {
-    // let mut rng = rand::thread_rng();
+// SplitMix64, which is plenty random for rolling dice
+struct Rng {
+    state: u64,
 }
 
-// impl Die for DiracDie {
-//     fn roll(&mut self) -> u32 {
-//         self.rng.gen() % 3 + 1
-//     }
-// }
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+}
+
+struct DiracDie {
+    faces: u32,
+    rng: Rng,
+}
 
 impl DiracDie {
-    fn get_distinct_permutations() -> Vec<([u32; 3], u32)> {
-        let mut sums = HashMap::new();
-        let mut outs = Vec::new();
-        let permutations = DiracDie::get_permutations();
-        for roll in permutations {
-            let sum = roll[0] + roll[1] + roll[2];
-            if !sums.contains_key(&sum) {
-                sums.insert(sum, 1);
-                outs.push(roll);
-            } else {
-                *sums.get_mut(&sum).unwrap() += 1;
-            }
+    fn new(faces: u32, seed: u64) -> DiracDie {
+        DiracDie {
+            faces,
+            rng: Rng { state: seed },
         }
+    }
+}
 
-        outs.iter()
-            .map(|roll| (*roll, *sums.get(&(roll[0] + roll[1] + roll[2])).unwrap()))
-            .collect()
+impl Die for DiracDie {
+    fn roll(&mut self) -> u32 {
+        (self.rng.next() % u64::from(self.faces)) as u32 + 1
     }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Rules {
+    board_size: u32,
+    die_faces: u32,
+    rolls_per_turn: u32,
+    winning_score: u32,
+}
 
-    fn get_permutations() -> Vec<[u32; 3]> {
-        let mut outs = Vec::new();
-        for i in 1..4 {
-            for j in 1..4 {
-                for k in 1..4 {
-                    outs.push([i, j, k]);
+impl Rules {
+    fn deterministic() -> Rules {
+        Rules {
+            board_size: 10,
+            die_faces: 100,
+            rolls_per_turn: 3,
+            winning_score: 1000,
+        }
+    }
+
+    fn dirac() -> Rules {
+        Rules {
+            board_size: 10,
+            die_faces: 3,
+            rolls_per_turn: 3,
+            winning_score: 21,
+        }
+    }
+
+    fn make_move(&self, pos: u32, movement: u32) -> u32 {
+        (pos - 1 + movement) % self.board_size + 1
+    }
+
+    // Every possible sum of a turn's rolls, and in how many universes it happens
+    fn roll_outcomes(&self) -> Vec<(u32, u128)> {
+        let mut counts = HashMap::from([(0, 1)]);
+        for _ in 0..self.rolls_per_turn {
+            let mut next = HashMap::new();
+            for (sum, count) in counts {
+                for face in 1..=self.die_faces {
+                    *next.entry(sum + face).or_insert(0) += count;
                 }
             }
+            counts = next;
         }
-        outs
+        counts.into_iter().sorted().collect()
+    }
+}
+
+fn parse_input(input: &str) -> Vec<u32> {
+    input
+        .lines()
+        .filter_map(|s| s.split_once(": ")?.1.trim().parse().ok())
+        .collect()
+}
+
+// What happened in a single turn, along with the state of the board after it
+#[derive(Clone, PartialEq, Debug)]
+struct Turn {
+    player: usize,
+    rolls: Vec<u32>,
+    positions: Vec<u32>,
+    scores: Vec<u32>,
+}
+
+struct Game {
+    rules: Rules,
+    positions: Vec<u32>,
+    scores: Vec<u32>,
+    turns: Vec<Turn>,
+    rolls: u32,
+    winner: Option<usize>,
+}
+
+impl Game {
+    // Needs at least one player, and everyone has to start on the board
+    fn new(rules: Rules, start: &[u32]) -> Option<Game> {
+        if start.is_empty() || start.iter().any(|p| !(1..=rules.board_size).contains(p)) {
+            return None;
+        }
+        Some(Game {
+            rules,
+            positions: start.to_vec(),
+            scores: vec![0; start.len()],
+            turns: Vec::new(),
+            rolls: 0,
+            winner: None,
+        })
+    }
+
+    fn take_turn(&mut self, die: &mut impl Die) {
+        let player = self.turns.len() % self.positions.len();
+        let rolls = (0..self.rules.rolls_per_turn)
+            .map(|_| die.roll())
+            .collect::<Vec<u32>>();
+        self.rolls += self.rules.rolls_per_turn;
+        self.positions[player] = self
+            .rules
+            .make_move(self.positions[player], rolls.iter().sum());
+        self.scores[player] += self.positions[player];
+        if self.rules.winning_score <= self.scores[player] {
+            self.winner = Some(player);
+        }
+        self.turns.push(Turn {
+            player,
+            rolls,
+            positions: self.positions.clone(),
+            scores: self.scores.clone(),
+        });
+    }
+
+    fn play(&mut self, die: &mut impl Die) -> usize {
+        loop {
+            if let Some(winner) = self.winner {
+                return winner;
+            }
+            self.take_turn(die);
+        }
+    }
+
+    // The lowest score of the losing players times the number of die rolls
+    fn loser_score_times_rolls(&self) -> Option<u32> {
+        let winner = self.winner?;
+        let loser_score = self
+            .scores
+            .iter()
+            .enumerate()
+            .filter(|(player, _)| *player != winner)
+            .map(|(_, score)| *score)
+            .min()?;
+        Some(loser_score * self.rolls)
+    }
+
+    // Looks up in the log which turn would have ended the game if the winning
+    // score was lower, without having to play it again.
+    fn first_turn_reaching(&self, score: u32) -> Option<(usize, &Turn)> {
+        self.turns
+            .iter()
+            .enumerate()
+            .find(|(_, turn)| score <= turn.scores[turn.player])
     }
 }
 
 fn main() {
     // const INPUT: &str = "Player 1 starting position: 4\nPlayer 2 starting position: 8";
     const INPUT: &str = include_str!("../inputs/21.txt");
-    let ((_, pos1), (_, pos2)): ((u32, u32), (u32, u32)) = INPUT
-        .lines()
-        .map(|s| {
-            s.matches(char::is_numeric)
-                .filter_map(|p| p.parse().ok())
-                .collect_tuple()
-                .unwrap()
-        })
-        .collect_tuple()
-        .unwrap();
-
-    let mut die = DeterministicDie::new();
-    let final_scores = play([pos1, pos2], &mut die);
-    let (winner, score) = determine_winner(&final_scores).unwrap();
-    println!("The winner is {}, which won with {} points.", winner, score);
-    let looser_score = if final_scores[0] == score {
-        final_scores[1]
-    } else {
-        final_scores[0]
+    let start = parse_input(INPUT);
+
+    let mut game = match Game::new(Rules::deterministic(), &start) {
+        Some(game) => game,
+        None => {
+            eprintln!("Expected starting positions on the board, got {:?}", start);
+            std::process::exit(1);
+        }
     };
+    let winner = game.play(&mut DeterministicDie::new(game.rules.die_faces));
+    println!(
+        "The winner is Player {}, which won with {} points after {} turns.",
+        winner + 1,
+        game.scores[winner],
+        game.turns.len()
+    );
     println!(
-        "Which means the looser times the dice roll ended up as: {} * {} = {}",
-        looser_score,
-        die.counter - 1,
-        looser_score * (die.counter - 1)
+        "Which means the looser times the dice roll ended up as: {}",
+        game.loser_score_times_rolls().unwrap()
     );
 
-    let dirac_die_combinations = DiracDie::get_distinct_permutations();
-    let win_counts = count_win_permutations([pos1, pos2], [0, 0], &dirac_die_combinations, 0, 21);
+    // What if the game was played to another score?
+    if let Some(target) = std::env::args().nth(1).and_then(|a| a.parse().ok()) {
+        if let Some((i, turn)) = game.first_turn_reaching(target) {
+            println!(
+                "Playing to {} points, Player {} would have won on turn {} with the scores {:?}",
+                target,
+                turn.player + 1,
+                i + 1,
+                turn.scores
+            );
+        } else {
+            let rules = Rules {
+                winning_score: target,
+                ..Rules::deterministic()
+            };
+            let mut game = Game::new(rules, &start).unwrap();
+            let winner = game.play(&mut DeterministicDie::new(rules.die_faces));
+            println!(
+                "Playing to {} points, Player {} wins after {} rolls, giving {}",
+                target,
+                winner + 1,
+                game.rolls,
+                game.loser_score_times_rolls().unwrap()
+            );
+        }
+    }
+
+    let rules = Rules::dirac();
+    let win_counts = count_quantum_wins(&rules, &start);
     println!(
         "Player 1 won {} times, and player 2 won {} times",
         win_counts[0], win_counts[1]
     );
-}
 
-fn make_move(rolls: [u32; 3], pos: u32) -> u32 {
-    let movement: u32 = rolls.iter().sum();
-    (pos - 1 + movement) % 10 + 1
+    const GAMES: u32 = 100000;
+    let mut die = DiracDie::new(rules.die_faces, 21);
+    let estimated = monte_carlo_win_rates(&rules, &start, &mut die, GAMES);
+    let exact = quantum_win_probabilities(&rules, &start);
+    for (player, (estimated, exact)) in estimated.iter().zip(exact).enumerate() {
+        println!(
+            "Player {} wins {:.4} of {} random games, expected {:.4}",
+            player + 1,
+            estimated,
+            GAMES,
+            exact
+        );
+    }
 }
 
-fn determine_winner(scores: &[u32; 2]) -> Option<(&'static str, u32)> {
-    if 1000 <= scores[0] {
-        Some(("Player 1", scores[0]))
-    } else if 1000 <= scores[1] {
-        Some(("Player 2", scores[1]))
-    } else {
-        None
+fn monte_carlo_win_rates(rules: &Rules, start: &[u32], die: &mut impl Die, games: u32) -> Vec<f64> {
+    let mut wins = vec![0; start.len()];
+    for _ in 0..games {
+        wins[Game::new(*rules, start).unwrap().play(die)] += 1;
     }
+    wins.iter()
+        .map(|w| f64::from(*w) / f64::from(games))
+        .collect()
 }
 
-fn play(mut pos: [u32; 2], die: &mut impl Die) -> [u32; 2] {
-    let mut scores = [0; 2];
-    let mut current_player = 0;
-    loop {
-        pos[current_player] = make_move([die.roll(), die.roll(), die.roll()], pos[current_player]);
-        scores[current_player] += pos[current_player];
-        if 1000 <= scores[current_player] {
-            return scores;
-        }
-        current_player = if current_player == 0 { 1 } else { 0 };
+type GameState = (Vec<u32>, Vec<u32>, usize);
+
+// Memoised over (positions, scores, current player), as the same state is
+// reached through a huge amount of different universes.
+fn count_wins<T>(
+    rules: &Rules,
+    outcomes: &[(u32, T)],
+    state: GameState,
+    memo: &mut HashMap<GameState, Vec<T>>,
+) -> Vec<T>
+where
+    T: Copy + Default + AddAssign + Mul<Output = T>,
+{
+    if let Some(wins) = memo.get(&state) {
+        return wins.clone();
     }
-}
 
-fn count_win_permutations(
-    pos: [u32; 2],
-    scores: [u32; 2],
-    possible_moves: &Vec<([u32; 3], u32)>,
-    current_player: usize,
-    winning_score: u32,
-) -> [u128; 2] {
-    let mut wins = [0, 0];
-    for (roll, permutations) in possible_moves {
-        let mut new_pos = pos;
-        let mut new_scores = scores;
-        let permutations = u128::from(*permutations);
-        new_pos[current_player] = make_move(*roll, pos[current_player]);
-        new_scores[current_player] += new_pos[current_player];
-        if winning_score <= new_scores[current_player] {
-            wins[current_player] += permutations;
+    let (pos, scores, current_player) = &state;
+    let mut wins = vec![T::default(); pos.len()];
+    for (movement, weight) in outcomes {
+        let mut new_pos = pos.clone();
+        let mut new_scores = scores.clone();
+        new_pos[*current_player] = rules.make_move(pos[*current_player], *movement);
+        new_scores[*current_player] += new_pos[*current_player];
+        if rules.winning_score <= new_scores[*current_player] {
+            wins[*current_player] += *weight;
         } else {
-            let new_wins = count_win_permutations(
-                new_pos,
-                new_scores,
-                possible_moves,
-                if current_player == 0 { 1 } else { 0 },
-                winning_score,
-            );
-            wins[0] += permutations * new_wins[0];
-            wins[1] += permutations * new_wins[1];
+            let next_player = (current_player + 1) % pos.len();
+            let new_wins = count_wins(rules, outcomes, (new_pos, new_scores, next_player), memo);
+            for (w, n) in wins.iter_mut().zip(new_wins) {
+                *w += *weight * n;
+            }
         }
     }
+    memo.insert(state, wins.clone());
     wins
 }
 
+fn count_quantum_wins(rules: &Rules, start: &[u32]) -> Vec<u128> {
+    let state = (start.to_vec(), vec![0; start.len()], 0);
+    count_wins(rules, &rules.roll_outcomes(), state, &mut HashMap::new())
+}
+
+// Unlike the universe counts, this weighs every universe by how likely it is
+fn quantum_win_probabilities(rules: &Rules, start: &[u32]) -> Vec<f64> {
+    let universes_per_turn = rules.roll_outcomes().iter().map(|(_, n)| n).sum::<u128>() as f64;
+    let outcomes = rules
+        .roll_outcomes()
+        .into_iter()
+        .map(|(sum, n)| (sum, n as f64 / universes_per_turn))
+        .collect::<Vec<(u32, f64)>>();
+    let state = (start.to_vec(), vec![0; start.len()], 0);
+    count_wins(rules, &outcomes, state, &mut HashMap::new())
+}
+
 #[test]
 fn test_move() {
-    assert_eq!(make_move([1, 2, 1], 6), 10);
-    assert_eq!(make_move([1, 2, 2], 6), 1);
+    let rules = Rules::deterministic();
+    assert_eq!(rules.make_move(6, 1 + 2 + 1), 10);
+    assert_eq!(rules.make_move(6, 1 + 2 + 2), 1);
 }
 
 #[test]
 fn test_deterministic_die() {
-    let mut die = DeterministicDie { counter: 100 };
+    let mut die = DeterministicDie {
+        faces: 100,
+        counter: 100,
+    };
     assert_eq!(die.roll(), 100);
     assert_eq!(die.roll(), 1);
+
+    let mut die = DeterministicDie::new(6);
+    let rolls = (0..8).map(|_| die.roll()).collect::<Vec<u32>>();
+    assert_eq!(rolls, [1, 2, 3, 4, 5, 6, 1, 2]);
 }
 
 #[test]
 fn test_deterministic_game() {
     const INPUT: &str = "Player 1 starting position: 4\nPlayer 2 starting position: 8";
-    let ((_, pos1), (_, pos2)): ((u32, u32), (u32, u32)) = INPUT
-        .lines()
-        .map(|s| {
-            s.matches(char::is_numeric)
-                .filter_map(|p| p.parse().ok())
-                .collect_tuple()
-                .unwrap()
-        })
-        .collect_tuple()
-        .unwrap();
+    let start = parse_input(INPUT);
+
+    let mut game = Game::new(Rules::deterministic(), &start).unwrap();
+    assert_eq!(
+        game.play(&mut DeterministicDie::new(game.rules.die_faces)),
+        0
+    );
+    assert_eq!(game.scores, [1000, 745]);
+    assert_eq!(game.rolls, 993);
+    assert_eq!(game.loser_score_times_rolls(), Some(739785));
+}
+
+#[test]
+fn test_game_log() {
+    let mut game = Game::new(Rules::deterministic(), &[4, 8]).unwrap();
+    game.play(&mut DeterministicDie::new(game.rules.die_faces));
+    assert_eq!(
+        game.turns[0],
+        Turn {
+            player: 0,
+            rolls: vec![1, 2, 3],
+            positions: vec![10, 8],
+            scores: vec![10, 0],
+        }
+    );
+    assert_eq!(game.turns[3].rolls, [10, 11, 12]);
+    assert_eq!(game.turns[3].scores, [14, 9]);
+    assert_eq!(game.turns.last().unwrap().scores, [1000, 745]);
 
-    let mut die = DeterministicDie::new();
-    let final_scores = play([pos1, pos2], &mut die);
-    let looser_score = final_scores.iter().min().unwrap();
-    assert_eq!(looser_score * (die.counter - 1), 739785);
+    // Stopping at a lower score in the log matches playing to that score
+    let (turn, _) = game.first_turn_reaching(500).unwrap();
+    let rules = Rules {
+        winning_score: 500,
+        ..Rules::deterministic()
+    };
+    let mut shorter = Game::new(rules, &[4, 8]).unwrap();
+    shorter.play(&mut DeterministicDie::new(rules.die_faces));
+    assert_eq!(shorter.turns.len(), turn + 1);
+    assert_eq!(&shorter.turns[..], &game.turns[..=turn]);
+}
+
+#[test]
+fn test_more_players() {
+    let mut game = Game::new(Rules::deterministic(), &[4, 8, 1, 5]).unwrap();
+    let winner = game.play(&mut DeterministicDie::new(game.rules.die_faces));
+    assert!(1000 <= game.scores[winner]);
+    assert_eq!(game.scores.iter().filter(|s| 1000 <= **s).count(), 1);
+    assert_eq!(game.rolls as usize, game.turns.len() * 3);
+    assert_eq!(game.turns.last().unwrap().player, winner);
+}
+
+#[test]
+fn test_bad_start() {
+    assert!(Game::new(Rules::deterministic(), &[]).is_none());
+    assert!(Game::new(Rules::deterministic(), &[4, 0]).is_none());
+    assert!(Game::new(Rules::deterministic(), &[11, 4]).is_none());
 }
 
 #[test]
 fn test_dirac_die_permutations() {
-    assert_eq!(DiracDie::get_permutations().iter().count(), 3*3*3);
+    let outcomes = Rules::dirac().roll_outcomes();
+    assert_eq!(outcomes.iter().map(|(_, n)| n).sum::<u128>(), 3 * 3 * 3);
+}
+
+#[test]
+fn test_roll_outcomes() {
+    let outcomes = Rules::dirac().roll_outcomes();
+    assert_eq!(
+        outcomes,
+        vec![(3, 1), (4, 3), (5, 6), (6, 7), (7, 6), (8, 3), (9, 1)]
+    );
 }
 
 #[test]
 fn test_dirac_die_outcomes() {
     const INPUT: &str = "Player 1 starting position: 4\nPlayer 2 starting position: 8";
-    let ((_, pos1), (_, pos2)): ((u32, u32), (u32, u32)) = INPUT
-        .lines()
-        .map(|s| {
-            s.matches(char::is_numeric)
-                .filter_map(|p| p.parse().ok())
-                .collect_tuple()
-                .unwrap()
-        })
-        .collect_tuple()
-        .unwrap();
-        
-    let dirac_die_combinations = DiracDie::get_distinct_permutations();
-    let win_counts = count_win_permutations([pos1, pos2], [0, 0], &dirac_die_combinations, 0, 21);
+    let start = parse_input(INPUT);
+    let win_counts = count_quantum_wins(&Rules::dirac(), &start);
 
     assert_eq!(win_counts, [444356092776315, 341960390180808]);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_single_universe() {
+    // With a one-sided die there's only one universe, which must match a normal game
+    struct OneDie;
+    impl Die for OneDie {
+        fn roll(&mut self) -> u32 {
+            1
+        }
+    }
+    let rules = Rules {
+        board_size: 7,
+        die_faces: 1,
+        rolls_per_turn: 2,
+        winning_score: 30,
+    };
+    let start = [1, 4, 6];
+    let winner = Game::new(rules, &start).unwrap().play(&mut OneDie);
+    let mut expected = vec![0; 3];
+    expected[winner] = 1;
+    assert_eq!(count_quantum_wins(&rules, &start), expected);
+}
+
+#[test]
+fn test_monte_carlo() {
+    let rules = Rules::dirac();
+    let start = [4, 8];
+    let exact = quantum_win_probabilities(&rules, &start);
+    assert!((exact.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+
+    let mut die = DiracDie::new(rules.die_faces, 1);
+    let estimated = monte_carlo_win_rates(&rules, &start, &mut die, 20000);
+    for (e, x) in estimated.iter().zip(exact) {
+        assert!((e - x).abs() < 0.02);
+    }
+}
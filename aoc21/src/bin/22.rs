@@ -1,32 +1,168 @@
 use itertools::Itertools;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
-use std::cmp;
 
-// enum SetCube {
-//     Cube([std::ops::Range<i64>; 3]),
-//     UnionCube((Box<SetCube>, Box<SetCube>)),
-//     DifferenceCube((Box<SetCube>, Box<SetCube>))
-// }
+// Half-open box [min, max) in N dimensions
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Cuboid<const N: usize> {
+    min: [i64; N],
+    max: [i64; N],
+}
+
+impl<const N: usize> Cuboid<N> {
+    fn new(min: [i64; N], max: [i64; N]) -> Self {
+        Cuboid { min, max }
+    }
 
-// fn union_size(a: SetCube, b: SetCube) -> u128 {
-    
-// }
+    fn is_empty(&self) -> bool {
+        (0..N).any(|d| self.max[d] <= self.min[d])
+    }
+
+    fn volume(&self) -> i128 {
+        if self.is_empty() {
+            0
+        } else {
+            (0..N)
+                .map(|d| (self.max[d] - self.min[d]) as i128)
+                .product()
+        }
+    }
 
-// fn difference_size(a: SetCube, b: SetCube) -> u128 {
+    fn intersection(&self, other: &Self) -> Option<Self> {
+        let cuboid = Cuboid {
+            min: std::array::from_fn(|d| self.min[d].max(other.min[d])),
+            max: std::array::from_fn(|d| self.max[d].min(other.max[d])),
+        };
+        if cuboid.is_empty() {
+            None
+        } else {
+            Some(cuboid)
+        }
+    }
 
-// }
+    // Splits self minus other into disjoint slabs: for each axis in turn,
+    // cut off the parts below and above other, then continue with the middle.
+    fn subtract(&self, other: &Self) -> Vec<Self> {
+        let overlap = match self.intersection(other) {
+            Some(overlap) => overlap,
+            None => return vec![*self],
+        };
 
-// fn cube_size(cube: SetCube) -> u128 {
-//     match cube {
-//         SetCube::Cube(range) => range_size(&range[0]) * range_size(&range[1]) * range_size(&range[2]),
-//         SetCube::UnionCube((a, b)) => union_size(*a, *b),
-//         SetCube::DifferenceCube((a, b)) => difference_size(*a, *b),
-//     }
-// }
+        let mut pieces = Vec::with_capacity(2 * N);
+        let mut rest = *self;
+        for d in 0..N {
+            if rest.min[d] < overlap.min[d] {
+                let mut below = rest;
+                below.max[d] = overlap.min[d];
+                pieces.push(below);
+            }
+            if overlap.max[d] < rest.max[d] {
+                let mut above = rest;
+                above.min[d] = overlap.max[d];
+                pieces.push(above);
+            }
+            rest.min[d] = overlap.min[d];
+            rest.max[d] = overlap.max[d];
+        }
+        pieces
+    }
+}
+
+// A set of points kept as a list of disjoint cuboids
+#[derive(Clone, Default, Debug)]
+struct CuboidSet<const N: usize> {
+    cuboids: Vec<Cuboid<N>>,
+}
+
+impl<const N: usize> CuboidSet<N> {
+    fn off(&mut self, cuboid: &Cuboid<N>) {
+        self.cuboids = self
+            .cuboids
+            .iter()
+            .flat_map(|c| c.subtract(cuboid))
+            .collect();
+    }
+
+    fn on(&mut self, cuboid: &Cuboid<N>) {
+        self.off(cuboid);
+        if !cuboid.is_empty() {
+            self.cuboids.push(*cuboid);
+        }
+    }
+
+    fn apply(&mut self, (state, cuboid): &(bool, Cuboid<N>)) {
+        if *state {
+            self.on(cuboid);
+        } else {
+            self.off(cuboid);
+        }
+    }
+
+    fn clip(&self, region: &Cuboid<N>) -> Self {
+        CuboidSet {
+            cuboids: self
+                .cuboids
+                .iter()
+                .filter_map(|c| c.intersection(region))
+                .collect(),
+        }
+    }
+
+    fn volume(&self) -> i128 {
+        self.cuboids.iter().map(Cuboid::volume).sum()
+    }
+}
+
+fn reboot(instructions: &[(bool, Cuboid<3>)]) -> CuboidSet<3> {
+    let mut set = CuboidSet::default();
+    for instruction in instructions {
+        set.apply(instruction);
+    }
+    set
+}
+
+fn initialization_area() -> Cuboid<3> {
+    Cuboid::new([-50; 3], [51; 3])
+}
 
 fn main() {
-    const INPUT: &str = "on x=-5..47,y=-31..22,z=-19..33
+    const INPUT: &str = include_str!("../inputs/22.txt");
+    let instructions = parse_input(INPUT);
+    let reactor = reboot(&instructions);
+
+    println!(
+        "Cubes turned on in area -50..50: {}",
+        reactor.clip(&initialization_area()).volume()
+    );
+    println!("Cubes turned on in total: {}", reactor.volume());
+}
+
+fn parse_input(input: &str) -> Vec<(bool, Cuboid<3>)> {
+    let mut outs = Vec::with_capacity(input.lines().count());
+    let re = Regex::new(r"(\-*\d+)\.\.(\-*\d+)").unwrap();
+    for line in input.lines() {
+        if let Some((x, y, z)) = re
+            .captures_iter(line)
+            .map(|cap| {
+                (
+                    cap[1].parse::<i64>().unwrap(),
+                    cap[2].parse::<i64>().unwrap() + 1,
+                )
+            })
+            .collect_tuple()
+        {
+            let cuboid = Cuboid::new([x.0, y.0, z.0], [x.1, y.1, z.1]);
+            if line.starts_with("on") {
+                outs.push((true, cuboid));
+            } else if line.starts_with("off") {
+                outs.push((false, cuboid));
+            }
+        }
+    }
+    outs
+}
+
+#[cfg(test)]
+const LARGE_EXAMPLE: &str = "on x=-5..47,y=-31..22,z=-19..33
 on x=-44..5,y=-27..21,z=-14..35
 on x=-49..-1,y=-11..42,z=-10..38
 on x=-20..34,y=-40..6,z=-44..1
@@ -87,118 +223,6 @@ off x=-70369..-16548,y=22648..78696,z=-1892..86821
 on x=-53470..21291,y=-120233..-33476,z=-44150..38147
 off x=-93533..-4276,y=-16170..68771,z=-104985..-24507";
 
-    // const INPUT: &str = include_str!("../inputs/22.txt");
-
-    let instructions = parse_input(INPUT);
-    
-    println!("Cubes turned on in area -50..50: {}", count_ons_in_50_area(&instructions));
-    println!("Cubes turned on in in total: {}", count_all_ons(&instructions));
-}
-
-type Cube = [std::ops::Range<i64>; 3];
-
-fn union_cube(a: &[std::ops::Range<i64>; 3], b: &[std::ops::Range<i64>; 3]) ->[std::ops::Range<i64>; 3] {
-    [
-        cmp::max(a[0].start, b[0].start)..cmp::min(a[0].end, b[0].end),
-        cmp::max(a[1].start, b[1].start)..cmp::min(a[1].end, b[1].end),
-        cmp::max(a[2].start, b[2].start)..cmp::min(a[2].end, b[2].end),
-    ]
-}
-
-fn range_size(range: &std::ops::Range<i64>) -> i64 {
-    if range.is_empty() {
-        0
-    } else {
-        range.end - range.start
-    }
-}
-
-fn cube_size(ranges: &Cube) -> i64 {
-    range_size(&ranges[0]) * range_size(&ranges[1]) * range_size(&ranges[2])
-}
-
-fn difference_cube_size(cube: &Cube, excluding_set: &Vec<Cube>) -> i64 {
-    let mut size = cube_size(&cube);
-    let mut detracted_sets = Vec::new();
-    for other_cube in excluding_set {
-        let u = union_cube(&cube, &other_cube);
-        let s = cube_size(&u);
-        // Detract the union of sets minus the union of sets that has already been subtracted
-        size -= i64::max(0, s - detracted_sets.iter().map(|neg|cube_size(&union_cube(neg, &u))).sum::<i64>());
-        detracted_sets.push(u);
-    }
-    size
-}
-
-fn count_all_ons(instructions: &Vec<(bool, [std::ops::Range<i64>; 3])>) -> u128 {
-    // let mut ons = 0;
-    // let mut on_ranges = Vec::new();
-    // let mut off_ranges = Vec::new();
-    // for (state, cube) in instructions {
-    //     if *state {
-    //         on_ranges.push(cube.clone());
-    //     } else {
-    //         off_ranges.push(cube.clone());
-    //     }
-    // }
-    // for cube in on_ranges {
-    //     ons += u128::try_from(difference_cube_size(&cube, &off_ranges)).unwrap();
-    // }
-
-    // ons
-    let mut ons = HashSet::new();
-    for (state, [xs, ys, zs]) in instructions {
-        for z in zs.clone() {
-            for y in ys.clone() {
-                for x in xs.clone() {
-                    if *state {
-                        ons.insert((x,y,z));
-                    } else {
-                        ons.remove(&(x,y,z));
-                    }
-                }
-            }
-        }
-    }
-    u128::try_from(ons.len()).unwrap()
-}
-
-fn count_ons_in_50_area(instructions: &Vec<(bool, [std::ops::Range<i64>; 3])>) -> u64 {
-    let mut states = HashMap::new();
-    for (state, [xs, ys, zs]) in instructions {
-        for z in zs.clone() {
-            if z < -50 || 50 < z { break; }
-            for y in ys.clone() {
-                if y < -50 || 50 < y { break; }
-                for x in xs.clone() {
-                    if x < -50 || 50 < x { break; }
-                    states.insert((x,y,z), *state);
-                }
-            }
-        }
-    }
-    states.values().map(|v| if *v { 1 } else { 0 }).sum::<u64>()
-}
-
-fn parse_input(input: &str) -> Vec<(bool, [std::ops::Range<i64>; 3])> {
-    let mut outs = Vec::with_capacity(input.lines().count());
-    let re = Regex::new(r"(\-*\d+)\.\.(\-*\d+)").unwrap();
-    for line in input.lines() {
-        if let Some((x, y, z)) = re
-            .captures_iter(line)
-            .map(|cap| cap[1].parse::<i64>().unwrap()..(cap[2].parse::<i64>().unwrap()+1))
-            .collect_tuple()
-        {
-            if line.starts_with("on") {
-                outs.push((true, [x, y, z]));
-            } else if line.starts_with("off") {
-                outs.push((false, [x, y, z]));
-            }
-        }
-    }
-    outs
-}
-
 #[test]
 fn test_count_limited_area() {
     const INPUT: &str = "on x=-20..26,y=-36..17,z=-47..7
@@ -225,5 +249,40 @@ on x=-54112..-39298,y=-85059..-49293,z=-27449..7877
 on x=967..23432,y=45373..81175,z=27513..53682";
 
     let instructions = parse_input(INPUT);
-    assert_eq!(count_ons_in_50_area(&instructions), 590784);
-}
\ No newline at end of file
+    assert_eq!(
+        reboot(&instructions).clip(&initialization_area()).volume(),
+        590784
+    );
+}
+
+#[test]
+fn test_large_example() {
+    let reactor = reboot(&parse_input(LARGE_EXAMPLE));
+    assert_eq!(reactor.clip(&initialization_area()).volume(), 474140);
+    assert_eq!(reactor.volume(), 2758514936282235);
+}
+
+#[test]
+fn test_subtract() {
+    let a = Cuboid::new([0, 0, 0], [3, 3, 3]);
+    let pieces = a.subtract(&Cuboid::new([1, 1, 1], [2, 2, 2]));
+    assert_eq!(pieces.len(), 6);
+    assert_eq!(pieces.iter().map(Cuboid::volume).sum::<i128>(), 26);
+    assert!(pieces
+        .iter()
+        .tuple_combinations()
+        .all(|(p, q)| p.intersection(q).is_none()));
+
+    assert_eq!(a.subtract(&Cuboid::new([5, 5, 5], [6, 6, 6])), vec![a]);
+    assert!(a.subtract(&Cuboid::new([-1, -1, -1], [4, 4, 4])).is_empty());
+}
+
+#[test]
+fn test_two_dimensions() {
+    let mut set = CuboidSet::default();
+    set.on(&Cuboid::new([0, 0], [4, 4]));
+    set.on(&Cuboid::new([2, 2], [6, 6]));
+    set.off(&Cuboid::new([3, 0], [4, 10]));
+    assert_eq!(set.volume(), 16 + 16 - 4 - 4 - 2);
+    assert_eq!(set.clip(&Cuboid::new([0, 0], [2, 2])).volume(), 4);
+}
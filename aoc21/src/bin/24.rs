@@ -1,12 +1,15 @@
 // Merry christmas!
 use itertools::Itertools;
 use std::collections::VecDeque;
+use std::fmt;
 
+#[derive(Clone, PartialEq, Debug)]
 enum Value {
     Literal(i64),
     Variable(usize),
 }
 
+#[derive(Clone, PartialEq, Debug)]
 enum Instruction {
     Inp(Value),
     Add(Value, Value),
@@ -97,9 +100,439 @@ impl ALU {
     }
 }
 
+fn register_name(i: usize) -> char {
+    (b'w' + i as u8) as char
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Variable(i) => write!(f, "{}", register_name(*i)),
+            Value::Literal(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+impl Instruction {
+    fn name(&self) -> &'static str {
+        match self {
+            Instruction::Inp(_) => "inp",
+            Instruction::Add(..) => "add",
+            Instruction::Mul(..) => "mul",
+            Instruction::Div(..) => "div",
+            Instruction::Mod(..) => "mod",
+            Instruction::Eql(..) => "eql",
+        }
+    }
+
+    fn operands(&self) -> Vec<&Value> {
+        use Instruction::{Add, Div, Eql, Inp, Mod, Mul};
+        match self {
+            Inp(a) => vec![a],
+            Add(a, b) | Mul(a, b) | Div(a, b) | Mod(a, b) | Eql(a, b) => vec![a, b],
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.name(), self.operands().iter().join(" "))
+    }
+}
+
+// Decompiler: symbolically executes each input block, starting with the
+// registers as unknowns, and simplifies the resulting expressions using the
+// value ranges they can take (inputs are always digits 1-9).
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Op {
+    Add,
+    Mul,
+    Div,
+    Mod,
+    Eql,
+    Neq,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Expr {
+    Lit(i64),
+    // Value read by the `inp` instruction into the given register
+    Input(usize),
+    // Value of the register at the start of the block
+    Reg(usize),
+    Bin(Op, Box<Expr>, Box<Expr>),
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+fn bin(op: Op, a: Expr, b: Expr) -> Expr {
+    Expr::Bin(op, Box::new(a), Box::new(b))
+}
+
+fn apply(op: Op, a: Expr, b: Expr) -> Expr {
+    use Expr::Lit;
+    match (op, a, b) {
+        (Op::Add, Lit(a), Lit(b)) => Lit(a + b),
+        (Op::Add, Lit(0), e) | (Op::Add, e, Lit(0)) => e,
+        (Op::Add, Lit(a), e) => apply(Op::Add, e, Lit(a)),
+        (Op::Add, Expr::Bin(Op::Add, e, c), Lit(b)) if matches!(*c, Lit(_)) => {
+            apply(Op::Add, *e, apply(Op::Add, *c, Lit(b)))
+        }
+        (Op::Mul, Lit(a), Lit(b)) => Lit(a * b),
+        (Op::Mul, Lit(0), _) | (Op::Mul, _, Lit(0)) => Lit(0),
+        (Op::Mul, Lit(1), e) | (Op::Mul, e, Lit(1)) => e,
+        (Op::Mul, Lit(a), e) => apply(Op::Mul, e, Lit(a)),
+        (Op::Div, Lit(a), Lit(b)) if b != 0 => Lit(a / b),
+        (Op::Div, e, Lit(1)) => e,
+        (Op::Div, e, Lit(b)) if b > 0 && e.range().0 >= 0 && e.range().1 < b => Lit(0),
+        (Op::Mod, Lit(a), Lit(b)) if b > 0 => Lit(a % b),
+        (Op::Mod, e, Lit(b)) if b > 0 && e.range().0 >= 0 && e.range().1 < b => e,
+        (Op::Eql | Op::Neq, a, b) if a == b => Lit((op == Op::Eql) as i64),
+        (Op::Eql | Op::Neq, a, b) if disjoint(a.range(), b.range()) => Lit((op == Op::Neq) as i64),
+        (Op::Eql, Expr::Bin(Op::Eql, a, b), Lit(0)) => bin(Op::Neq, *a, *b),
+        (Op::Eql, Expr::Bin(Op::Neq, a, b), Lit(0)) => bin(Op::Eql, *a, *b),
+        (Op::Eql, e, Lit(1)) if e.is_condition() => e,
+        (op, a, b) => bin(op, a, b),
+    }
+}
+
+fn disjoint((a_min, a_max): (i64, i64), (b_min, b_max): (i64, i64)) -> bool {
+    a_max < b_min || b_max < a_min
+}
+
+impl Expr {
+    fn is_condition(&self) -> bool {
+        matches!(self, Expr::Bin(Op::Eql | Op::Neq, _, _))
+    }
+
+    fn negated(&self) -> Option<Expr> {
+        match self {
+            Expr::Bin(Op::Eql, a, b) => Some(Expr::Bin(Op::Neq, a.clone(), b.clone())),
+            Expr::Bin(Op::Neq, a, b) => Some(Expr::Bin(Op::Eql, a.clone(), b.clone())),
+            _ => None,
+        }
+    }
+
+    // Inclusive lower and upper bound of the values the expression can take
+    fn range(&self) -> (i64, i64) {
+        match self {
+            Expr::Lit(n) => (*n, *n),
+            Expr::Input(_) => (1, 9),
+            Expr::Reg(_) => (i64::MIN, i64::MAX),
+            Expr::Bin(op, a, b) => {
+                let ((a_min, a_max), (b_min, b_max)) = (a.range(), b.range());
+                match op {
+                    Op::Add => (a_min.saturating_add(b_min), a_max.saturating_add(b_max)),
+                    Op::Mul => {
+                        let products = [
+                            a_min.saturating_mul(b_min),
+                            a_min.saturating_mul(b_max),
+                            a_max.saturating_mul(b_min),
+                            a_max.saturating_mul(b_max),
+                        ];
+                        products.iter().copied().minmax().into_option().unwrap()
+                    }
+                    Op::Div if b_min == b_max && b_min > 0 => (a_min / b_min, a_max / b_min),
+                    Op::Div => (i64::MIN, i64::MAX),
+                    // The ALU is only defined for a >= 0 and b > 0
+                    Op::Mod if b_max > 0 => (0, b_max - 1),
+                    Op::Mod => (0, i64::MAX),
+                    Op::Eql | Op::Neq => (0, 1),
+                }
+            }
+            Expr::If(_, a, b) => {
+                let ((a_min, a_max), (b_min, b_max)) = (a.range(), b.range());
+                (a_min.min(b_min), a_max.max(b_max))
+            }
+        }
+    }
+
+    fn find_condition(&self) -> Option<&Expr> {
+        match self {
+            Expr::Bin(_, _, _) if self.is_condition() => Some(self),
+            Expr::Bin(_, a, b) => a.find_condition().or_else(|| b.find_condition()),
+            Expr::If(c, a, b) => Some(c.as_ref())
+                .filter(|c| c.is_condition())
+                .or_else(|| a.find_condition())
+                .or_else(|| b.find_condition()),
+            _ => None,
+        }
+    }
+
+    // Rebuilds the expression as if `condition` had the given outcome
+    fn assume(&self, condition: &Expr, outcome: bool) -> Expr {
+        if self == condition {
+            return Expr::Lit(outcome as i64);
+        }
+        if condition.negated().as_ref() == Some(self) {
+            return Expr::Lit(!outcome as i64);
+        }
+        match self {
+            Expr::Bin(op, a, b) => apply(
+                *op,
+                a.assume(condition, outcome),
+                b.assume(condition, outcome),
+            ),
+            Expr::If(c, a, b) => match c.assume(condition, outcome) {
+                Expr::Lit(0) => b.assume(condition, outcome),
+                Expr::Lit(_) => a.assume(condition, outcome),
+                c => Expr::If(
+                    Box::new(c),
+                    Box::new(a.assume(condition, outcome)),
+                    Box::new(b.assume(condition, outcome)),
+                ),
+            },
+            e => e.clone(),
+        }
+    }
+
+    // Turns conditions hidden in arithmetic (like `y * (x != w)`) into branches
+    fn lift_conditions(&self) -> Expr {
+        if self.is_condition() {
+            return self.clone();
+        }
+        match self.find_condition() {
+            Some(condition) => {
+                let (then, otherwise) = (
+                    self.assume(condition, true).lift_conditions(),
+                    self.assume(condition, false).lift_conditions(),
+                );
+                if then == otherwise {
+                    then
+                } else {
+                    Expr::If(
+                        Box::new(condition.clone()),
+                        Box::new(then),
+                        Box::new(otherwise),
+                    )
+                }
+            }
+            None => self.clone(),
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Lit(n) if *n < 0 => 2,
+            Expr::Lit(_) | Expr::Input(_) | Expr::Reg(_) => 3,
+            Expr::Bin(Op::Mul | Op::Div | Op::Mod, _, _) => 2,
+            Expr::Bin(Op::Add, _, _) => 1,
+            Expr::Bin(Op::Eql | Op::Neq, _, _) | Expr::If(_, _, _) => 0,
+        }
+    }
+}
+
+fn fmt_operand(f: &mut fmt::Formatter, e: &Expr, min_precedence: u8) -> fmt::Result {
+    if e.precedence() < min_precedence {
+        write!(f, "({})", e)
+    } else {
+        write!(f, "{}", e)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Lit(n) => write!(f, "{}", n),
+            Expr::Input(i) | Expr::Reg(i) => write!(f, "{}", register_name(*i)),
+            Expr::Bin(Op::Add, a, b) => {
+                fmt_operand(f, a, 1)?;
+                match b.as_ref() {
+                    Expr::Lit(n) if *n < 0 => write!(f, " - {}", -n),
+                    b => {
+                        write!(f, " + ")?;
+                        fmt_operand(f, b, 1)
+                    }
+                }
+            }
+            Expr::Bin(op, a, b) => {
+                let (symbol, precedence) = match op {
+                    Op::Mul => ("*", 2),
+                    Op::Div => ("/", 2),
+                    Op::Mod => ("%", 2),
+                    Op::Eql => ("==", 3),
+                    Op::Neq => ("!=", 3),
+                    Op::Add => unreachable!(),
+                };
+                fmt_operand(f, a, precedence)?;
+                write!(f, " {} ", symbol)?;
+                // Integer division isn't associative, so the right side always
+                // needs parentheses if it's an operation of the same strength.
+                fmt_operand(f, b, 3)
+            }
+            Expr::If(c, a, b) => write!(f, "if {} {{ {} }} else {{ {} }}", c, a, b),
+        }
+    }
+}
+
+fn split_blocks(instructions: &[Instruction]) -> Vec<&[Instruction]> {
+    let mut starts = instructions
+        .iter()
+        .positions(|op| matches!(op, Instruction::Inp(_)))
+        .collect::<Vec<usize>>();
+    if starts.first() != Some(&0) {
+        starts.insert(0, 0);
+    }
+    starts.push(instructions.len());
+    starts
+        .iter()
+        .tuple_windows()
+        .map(|(&a, &b)| &instructions[a..b])
+        .filter(|block| !block.is_empty())
+        .collect()
+}
+
+fn symbolic_execute(instructions: &[Instruction]) -> [Expr; 4] {
+    use Instruction::{Add, Div, Eql, Inp, Mod, Mul};
+    use Value::{Literal, Variable};
+    let mut registers = [0, 1, 2, 3].map(Expr::Reg);
+    for op in instructions {
+        let (i, op, b) = match op {
+            Inp(Variable(i)) => {
+                registers[*i] = Expr::Input(*i);
+                continue;
+            }
+            Add(Variable(i), b) => (i, Op::Add, b),
+            Mul(Variable(i), b) => (i, Op::Mul, b),
+            Div(Variable(i), b) => (i, Op::Div, b),
+            Mod(Variable(i), b) => (i, Op::Mod, b),
+            Eql(Variable(i), b) => (i, Op::Eql, b),
+            _ => continue,
+        };
+        let b = match b {
+            Variable(j) => registers[*j].clone(),
+            Literal(n) => Expr::Lit(*n),
+        };
+        registers[*i] = apply(op, registers[*i].clone(), b);
+    }
+
+    registers.map(|e| e.lift_conditions())
+}
+
+fn decompile_block(instructions: &[Instruction]) -> Vec<String> {
+    symbolic_execute(instructions)
+        .iter()
+        .enumerate()
+        .filter(|(i, e)| **e != Expr::Reg(*i) && **e != Expr::Input(*i))
+        .map(|(i, e)| format!("{} = {}", register_name(i), e))
+        .collect()
+}
+
+// A sequence of instructions shared by every block, where only some of the
+// literals differ.
+struct BlockPattern {
+    template: Vec<Instruction>,
+    // (instruction index, operand index) of each varying literal
+    parameters: Vec<(usize, usize)>,
+    // Parameter values for each block
+    values: Vec<Vec<i64>>,
+}
+
+fn same_shape(a: &Instruction, b: &Instruction) -> bool {
+    a.name() == b.name()
+        && a.operands()
+            .iter()
+            .zip(b.operands())
+            .all(|(a, b)| match (a, b) {
+                (Value::Literal(_), Value::Literal(_)) => true,
+                (a, b) => *a == b,
+            })
+}
+
+fn find_block_pattern(blocks: &[&[Instruction]]) -> Option<BlockPattern> {
+    let template = *blocks.first()?;
+    if blocks.iter().any(|block| {
+        block.len() != template.len() || !block.iter().zip(template).all(|(a, b)| same_shape(a, b))
+    }) {
+        return None;
+    }
+
+    let literal = |block: &[Instruction], i: usize, j: usize| match block[i].operands()[j] {
+        Value::Literal(n) => Some(*n),
+        _ => None,
+    };
+    let parameters = template
+        .iter()
+        .enumerate()
+        .flat_map(|(i, op)| (0..op.operands().len()).map(move |j| (i, j)))
+        .filter(|&(i, j)| {
+            literal(template, i, j).is_some()
+                && !blocks.iter().map(|block| literal(block, i, j)).all_equal()
+        })
+        .collect::<Vec<(usize, usize)>>();
+    let values = blocks
+        .iter()
+        .map(|block| {
+            parameters
+                .iter()
+                .filter_map(|&(i, j)| literal(block, i, j))
+                .collect()
+        })
+        .collect();
+
+    Some(BlockPattern {
+        template: template.to_vec(),
+        parameters,
+        values,
+    })
+}
+
+impl fmt::Display for BlockPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "// {} blocks share a pattern of {} instructions with {} parameters:",
+            self.values.len(),
+            self.template.len(),
+            self.parameters.len()
+        )?;
+        for (i, op) in self.template.iter().enumerate() {
+            let operands = op
+                .operands()
+                .iter()
+                .enumerate()
+                .map(
+                    |(j, v)| match self.parameters.iter().position(|&p| p == (i, j)) {
+                        Some(p) => format!("p{}", p),
+                        None => v.to_string(),
+                    },
+                )
+                .join(" ");
+            writeln!(f, "{} {}", op.name(), operands)?;
+        }
+        write!(f, "// block")?;
+        for p in 0..self.parameters.len() {
+            write!(f, " {:>4}", format!("p{}", p))?;
+        }
+        for (i, values) in self.values.iter().enumerate() {
+            write!(f, "\n// {:>5}", i)?;
+            for v in values {
+                write!(f, " {:>4}", v)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn decompile(instructions: &[Instruction]) -> String {
+    let blocks = split_blocks(instructions);
+    let mut lines = Vec::new();
+    for (i, block) in blocks.iter().enumerate() {
+        lines.push(format!("// block {}", i));
+        lines.extend(decompile_block(block));
+    }
+    if blocks.len() > 1 {
+        if let Some(pattern) = find_block_pattern(&blocks) {
+            lines.push(pattern.to_string());
+        }
+    }
+    lines.join("\n")
+}
+
 fn main() {
     const INPUT: &str = include_str!("../inputs/24.txt");
     let alu = ALU::new(INPUT);
+    println!("{}", decompile(&alu.instructions));
 
     const MAX: u128 = 99999999999999;
     for i in 0..=MAX {
@@ -157,3 +590,37 @@ fn test3() {
         assert_eq!(n, i64::from_str_radix(&binary[..], 2).unwrap());
     }
 }
+
+#[test]
+fn test_decompile_push_block() {
+    const INPUT: &str = "inp w\nmul x 0\nadd x z\nmod x 26\ndiv z 1\nadd x 12\neql x w\neql x 0\nmul y 0\nadd y 25\nmul y x\nadd y 1\nmul z y\nmul y 0\nadd y w\nadd y 7\nmul y x\nadd z y";
+    let alu = ALU::new(INPUT);
+    assert_eq!(
+        decompile_block(&alu.instructions),
+        vec!["x = 1", "y = w + 7", "z = z * 26 + w + 7"]
+    );
+}
+
+#[test]
+fn test_decompile_pop_block() {
+    const INPUT: &str = "inp w\nmul x 0\nadd x z\nmod x 26\ndiv z 26\nadd x -8\neql x w\neql x 0\nmul y 0\nadd y 25\nmul y x\nadd y 1\nmul z y\nmul y 0\nadd y w\nadd y 10\nmul y x\nadd z y";
+    let alu = ALU::new(INPUT);
+    assert_eq!(
+        decompile_block(&alu.instructions)[2],
+        "z = if (z % 26 - 8) != w { z / 26 * 26 + w + 10 } else { z / 26 }"
+    );
+}
+
+#[test]
+fn test_block_pattern() {
+    const INPUT: &str = include_str!("../inputs/24.txt");
+    let alu = ALU::new(INPUT);
+    let blocks = split_blocks(&alu.instructions);
+    assert_eq!(blocks.len(), 14);
+
+    let pattern = find_block_pattern(&blocks).unwrap();
+    assert_eq!(pattern.template.len(), 18);
+    assert_eq!(pattern.parameters, vec![(4, 1), (5, 1), (15, 1)]);
+    assert_eq!(pattern.values[0], vec![1, 12, 7]);
+    assert_eq!(pattern.values[13], vec![26, -11, 5]);
+}
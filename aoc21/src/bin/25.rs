@@ -1,159 +1,250 @@
-use std::collections::HashSet;
+use common::automaton::{Automaton, Edges, Grid, Rule};
+use common::Pos;
 
 fn main() {
-//     const INPUT: &str = "v...>>.vv>
-// .vv>>.vv..
-// >>.>v>...v
-// >>v>>.>.v.
-// v>v.vv.v..
-// >.>>..v...
-// .vv..>.>v.
-// v.v..>>v.v
-// ....v..v.>";
+    //     const INPUT: &str = "v...>>.vv>
+    // .vv>>.vv..
+    // >>.>v>...v
+    // >>v>>.>.v.
+    // v>v.vv.v..
+    // >.>>..v...
+    // .vv..>.>v.
+    // v.v..>>v.v
+    // ....v..v.>";
     const INPUT: &str = include_str!("../inputs/25.txt");
+    let args: Vec<String> = std::env::args().collect();
 
-    let mut grid = parse_cucumbers(&INPUT);
+    // The cell by cell automaton is a lot slower, but handy for comparing against
+    if args.iter().any(|a| a == "--automaton") {
+        let mut cucumbers = Automaton::new(Herds, parse_cucumbers(INPUT));
+        match cucumbers.run_until(usize::MAX, |stats| stats.changed == 0) {
+            Some(stats) => println!("Cucumbers stable after {} iterations", stats.step),
+            None => println!("Cucumbers never stopped moving"),
+        }
+        println!("Final grid:");
+        println!("{}", render(&cucumbers.grid));
+        return;
+    }
+
+    let mut cucumbers = PackedHerds::parse(INPUT);
     println!("Initial grid:");
-    print_cucumbers(&grid);
+    println!("{}", cucumbers.render());
 
-    for i in 1.. {
-        let new_grid = move_cucumbers(&grid);
-        if grid_equals(&grid, &new_grid) {
-            println!("Cucumbers stable after {} iterations", i);
-            break;
+    let movers = cucumbers.run_until_still(usize::MAX);
+    if args.iter().any(|a| a == "--movers") {
+        for (step, n) in movers.iter().enumerate() {
+            println!("Step {}: {} cucumbers moved", step + 1, n);
         }
-        grid = new_grid;
+    }
+    match movers.last() {
+        Some(0) => println!("Cucumbers stable after {} iterations", movers.len()),
+        _ => println!("Cucumbers never stopped moving"),
     }
 
     println!("Final grid:");
-    print_cucumbers(&grid);
+    println!("{}", cucumbers.render());
 }
 
-type Coord = (i64, i64);
-
-#[derive(Clone, Copy, PartialEq)]
-enum Entity {
-    EastCucumber,
-    SouthCucumber,
-    Nothing,
+// Both herds as one bitset per row, where bit x of a row is column x. A
+// whole herd moves at once by checking every cucumber against the row
+// shifted by one column (east) or against the next row (south).
+#[derive(Clone, Debug, PartialEq)]
+struct PackedHerds {
+    width: usize,
+    east: Vec<Vec<u64>>,
+    south: Vec<Vec<u64>>,
 }
 
-fn parse_cucumbers(input: &str) -> Vec<Vec<Entity>> {
-    use Entity::{EastCucumber, Nothing, SouthCucumber};
-    input
-        .lines()
-        .map(|l| {
-            l.chars()
-                .map(|c| match c {
-                    '>' => EastCucumber,
-                    'v' => SouthCucumber,
-                    _ => Nothing,
-                })
-                .collect()
-        })
-        .collect()
-}
+impl PackedHerds {
+    fn parse(input: &str) -> PackedHerds {
+        let rows: Vec<&str> = input
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .collect();
+        let width = rows.first().map_or(0, |r| r.len());
+        let words = width.div_ceil(64);
+        let mut herds = PackedHerds {
+            width,
+            east: vec![vec![0; words]; rows.len()],
+            south: vec![vec![0; words]; rows.len()],
+        };
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let herd = match c {
+                    '>' => &mut herds.east,
+                    'v' => &mut herds.south,
+                    _ => continue,
+                };
+                herd[y][x / 64] |= 1 << (x % 64);
+            }
+        }
+        herds
+    }
 
-fn print_cucumbers(grid: &Vec<Vec<Entity>>) {
-    use Entity::{EastCucumber, Nothing, SouthCucumber};
-    for line in grid {
-        let ln: String = line
-            .iter()
-            .map(|e| match e {
-                EastCucumber => '>',
-                SouthCucumber => 'v',
-                Nothing => '.',
-            })
+    fn get(row: &[u64], x: usize) -> bool {
+        row[x / 64] >> (x % 64) & 1 == 1
+    }
+
+    // out[x] = row[x + 1], wrapping around at the end of the row
+    fn next_columns(&self, row: &[u64]) -> Vec<u64> {
+        let mut out: Vec<u64> = (0..row.len())
+            .map(|w| row[w] >> 1 | row.get(w + 1).map_or(0, |next| next << 63))
             .collect();
-        println!("{}", ln);
+        let last = self.width - 1;
+        out[last / 64] &= !(1 << (last % 64));
+        out[last / 64] |= (row[0] & 1) << (last % 64);
+        out
     }
-}
 
-fn move_cucumbers(grid: &Vec<Vec<Entity>>) -> Vec<Vec<Entity>> {
-    let (grid_width, grid_height) = (grid.iter().next().unwrap().len(), grid.len());
-    let mut new_grid = grid.clone();
-    for entity_coord in get_entity_coords(grid, &Entity::EastCucumber) {
-        let forward_coord: (i64, i64) = (
-            (entity_coord.0 + 1).try_into().unwrap(),
-            entity_coord.1.try_into().unwrap(),
-        );
-        if index(grid, forward_coord).unwrap() == &Entity::Nothing {
-            *index_mut(&mut new_grid, forward_coord).unwrap() = Entity::EastCucumber;
-            *index_mut(&mut new_grid, entity_coord).unwrap() = Entity::Nothing;
+    // out[x] = row[x - 1], wrapping around at the start of the row
+    fn previous_columns(&self, row: &[u64]) -> Vec<u64> {
+        let mut out: Vec<u64> = (0..row.len())
+            .map(|w| row[w] << 1 | if 0 < w { row[w - 1] >> 63 } else { 0 })
+            .collect();
+        let last = self.width - 1;
+        out[0] |= Self::get(row, last) as u64;
+        // The bit shifted past the end of the row
+        if !self.width.is_multiple_of(64) {
+            out[last / 64] &= (1 << (self.width % 64)) - 1;
+        }
+        out
+    }
+
+    // Moves the east herd, then the south one, returning how many moved
+    fn step(&mut self) -> usize {
+        let mut moved = 0;
+        let height = self.east.len();
+
+        for y in 0..height {
+            let free: Vec<u64> = self.east[y]
+                .iter()
+                .zip(&self.south[y])
+                .map(|(e, s)| !(e | s))
+                .collect();
+            let movers: Vec<u64> = self
+                .next_columns(&free)
+                .iter()
+                .zip(&self.east[y])
+                .map(|(f, e)| f & e)
+                .collect();
+            moved += movers
+                .iter()
+                .map(|w| w.count_ones() as usize)
+                .sum::<usize>();
+            let arrivals = self.previous_columns(&movers);
+            for ((e, m), a) in self.east[y].iter_mut().zip(&movers).zip(&arrivals) {
+                *e = (*e & !m) | a;
+            }
+        }
+
+        let movers: Vec<Vec<u64>> = (0..height)
+            .map(|y| {
+                let below = (y + 1) % height;
+                self.south[y]
+                    .iter()
+                    .zip(&self.east[below])
+                    .zip(&self.south[below])
+                    .map(|((s, e), b)| s & !(e | b))
+                    .collect()
+            })
+            .collect();
+        for y in 0..height {
+            let above = (y + height - 1) % height;
+            for (w, s) in self.south[y].iter_mut().enumerate() {
+                *s = (*s & !movers[y][w]) | movers[above][w];
+            }
+            moved += movers[y]
+                .iter()
+                .map(|w| w.count_ones() as usize)
+                .sum::<usize>();
         }
+        moved
     }
 
-    let mut new_grid_2 = new_grid.clone();
-    for entity_coord in get_entity_coords(&new_grid, &Entity::SouthCucumber) {
-        let forward_coord: (i64, i64) = (
-            entity_coord.0.try_into().unwrap(),
-            (entity_coord.1 + 1).try_into().unwrap(),
-        );
-        if index(&new_grid, forward_coord).unwrap() == &Entity::Nothing {
-            *index_mut(&mut new_grid_2, forward_coord).unwrap() = Entity::SouthCucumber;
-            *index_mut(&mut new_grid_2, entity_coord).unwrap() = Entity::Nothing;
+    // Cucumbers moved in every step, up to and including the first one where
+    // none of them did
+    fn run_until_still(&mut self, limit: usize) -> Vec<usize> {
+        let mut movers = Vec::new();
+        for _ in 0..limit {
+            let moved = self.step();
+            movers.push(moved);
+            if moved == 0 {
+                break;
+            }
         }
+        movers
     }
 
-    new_grid_2
+    fn render(&self) -> String {
+        self.east
+            .iter()
+            .zip(&self.south)
+            .map(|(e, s)| {
+                (0..self.width)
+                    .map(|x| match (Self::get(e, x), Self::get(s, x)) {
+                        (true, _) => '>',
+                        (_, true) => 'v',
+                        _ => '.',
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
 }
 
-fn get_entity_coords(grid: &Vec<Vec<Entity>>, entity: &Entity) -> HashSet<Coord> {
-    // Could just do some filters and maps on the list,
-    // but more idiomic, faster and easy to read this way:
-    let mut set = HashSet::new();
-    for (j, xs) in grid.iter().enumerate() {
-        for (i, x) in xs.iter().enumerate() {
-            if x == entity {
-                set.insert((i.try_into().unwrap(), j.try_into().unwrap()));
+#[derive(Clone, Copy, PartialEq)]
+enum Entity {
+    EastCucumber,
+    SouthCucumber,
+    Nothing,
+}
+
+// The east facing herd moves first, then the south facing one, each only
+// into spots that were free before the herd started moving.
+struct Herds;
+
+impl Rule for Herds {
+    type Cell = Entity;
+
+    fn phases(&self) -> usize {
+        2
+    }
+
+    fn update(&self, phase: usize, grid: &Grid<Entity>, pos: Pos) -> Entity {
+        let (herd, forward) = match phase {
+            0 => (Entity::EastCucumber, (1, 0)),
+            _ => (Entity::SouthCucumber, (0, 1)),
+        };
+        let backward = (-forward.0, -forward.1);
+        match *grid.get(pos) {
+            e if e == herd && grid.relative(pos, forward) == Some(&Entity::Nothing) => {
+                Entity::Nothing
             }
+            Entity::Nothing if grid.relative(pos, backward) == Some(&herd) => herd,
+            e => e,
         }
     }
-    set
 }
 
-fn index(grid: &Vec<Vec<Entity>>, (i, j): (i64, i64)) -> Option<&Entity> {
-    let (grid_width, grid_height) = (grid.iter().next()?.len(), grid.len());
-    let bounded_x: usize = (if i < 0 {
-        i64::try_from(grid_width).ok()? - i % i64::try_from(grid_width).ok()?
-    } else {
-        i % i64::try_from(grid_width).ok()?
-    })
-    .try_into()
-    .ok()?;
-    let bounded_y: usize = (if j < 0 {
-        i64::try_from(grid_height).ok()? - j % i64::try_from(grid_height).ok()?
-    } else {
-        j % i64::try_from(grid_height).ok()?
+fn parse_cucumbers(input: &str) -> Grid<Entity> {
+    use Entity::{EastCucumber, Nothing, SouthCucumber};
+    Grid::parse(input, |c| match c {
+        '>' => EastCucumber,
+        'v' => SouthCucumber,
+        _ => Nothing,
     })
-    .try_into()
-    .ok()?;
-    grid.get(bounded_y)?.get(bounded_x)
+    .with_edges(Edges::Wrap)
 }
 
-fn index_mut(grid: &mut Vec<Vec<Entity>>, (i, j): (i64, i64)) -> Option<&mut Entity> {
-    let (grid_width, grid_height) = (grid.iter().next()?.len(), grid.len());
-    let bounded_x: usize = (if i < 0 {
-        i64::try_from(grid_width).ok()? - i % i64::try_from(grid_width).ok()?
-    } else {
-        i % i64::try_from(grid_width).ok()?
-    })
-    .try_into()
-    .ok()?;
-    let bounded_y: usize = (if j < 0 {
-        i64::try_from(grid_height).ok()? - j % i64::try_from(grid_height).ok()?
-    } else {
-        j % i64::try_from(grid_height).ok()?
+fn render(grid: &Grid<Entity>) -> String {
+    use Entity::{EastCucumber, Nothing, SouthCucumber};
+    grid.render(|e| match e {
+        EastCucumber => '>',
+        SouthCucumber => 'v',
+        Nothing => '.',
     })
-    .try_into()
-    .ok()?;
-    grid.get_mut(bounded_y)?.get_mut(bounded_x)
-}
-
-fn grid_equals(a: &Vec<Vec<Entity>>, b: &Vec<Vec<Entity>>) -> bool {
-    !a.iter()
-        .zip(b.iter())
-        .any(|(ls, rs)| ls.iter().zip(rs.iter()).any(|(l, r)| l != r))
 }
 
 #[test]
@@ -178,9 +269,104 @@ v>>.>vvv..
 vv...>>vv.
 >.v.v..v.v";
 
-    let grid = parse_cucumbers(&INPUT);
-    assert!(grid_equals(
-        &move_cucumbers(&grid),
-        &parse_cucumbers(&RESULT)
-    ));
+    let mut cucumbers = Automaton::new(Herds, parse_cucumbers(INPUT));
+    cucumbers.step();
+    assert!(cucumbers.grid == parse_cucumbers(RESULT));
+}
+
+#[test]
+fn test_stable() {
+    const INPUT: &str = "v...>>.vv>
+.vv>>.vv..
+>>.>v>...v
+>>v>>.>.v.
+v>v.vv.v..
+>.>>..v...
+.vv..>.>v.
+v.v..>>v.v
+....v..v.>";
+
+    let mut cucumbers = Automaton::new(Herds, parse_cucumbers(INPUT));
+    let stats = cucumbers
+        .run_until(100, |stats| stats.changed == 0)
+        .unwrap();
+    assert_eq!(stats.step, 58);
+    assert_eq!(
+        render(&cucumbers.grid),
+        "..>>v>vv..
+..v.>>vv..
+..>>v>>vv.
+..>>>>>vv.
+v......>vv
+v>v....>>v
+vvv.....>>
+>vv......>
+.>v.vv.v.."
+    );
+}
+
+#[test]
+fn test_wrap_around() {
+    // Both herds move off one edge and reappear on the opposite one
+    let mut cucumbers = Automaton::new(Herds, parse_cucumbers("..>\n...\n.v."));
+    let stats = cucumbers.step();
+    assert_eq!(stats.changed, 4);
+    assert_eq!(render(&cucumbers.grid), ">v.\n...\n...");
+}
+
+#[test]
+fn test_packed_example() {
+    const INPUT: &str = "v...>>.vv>
+.vv>>.vv..
+>>.>v>...v
+>>v>>.>.v.
+v>v.vv.v..
+>.>>..v...
+.vv..>.>v.
+v.v..>>v.v
+....v..v.>";
+
+    let mut packed = PackedHerds::parse(INPUT);
+    assert_eq!(packed.render(), INPUT);
+    let movers = packed.run_until_still(100);
+    assert_eq!(movers.len(), 58);
+    assert_eq!(movers.last(), Some(&0));
+
+    let mut cucumbers = Automaton::new(Herds, parse_cucumbers(INPUT));
+    cucumbers.run_until(100, |stats| stats.changed == 0);
+    assert_eq!(packed.render(), render(&cucumbers.grid));
+}
+
+#[test]
+fn test_packed_matches_automaton() {
+    // Rows spanning several words, and a width that isn't a multiple of 64
+    let input = include_str!("../inputs/25.txt");
+    let mut packed = PackedHerds::parse(input);
+    let mut cucumbers = Automaton::new(Herds, parse_cucumbers(input));
+    assert_eq!(packed.width, 139);
+    for _ in 0..20 {
+        let moved = packed.step();
+        let stats = cucumbers.step();
+        // Every move empties one cell and fills another
+        assert_eq!(2 * moved, stats.changed);
+        assert_eq!(packed.render(), render(&cucumbers.grid));
+    }
+}
+
+#[test]
+fn test_packed_wrap_around() {
+    // Exactly one word wide, so the wrap happens on a word boundary. The
+    // cucumber at the end is blocked by the one at the start, which hasn't
+    // moved yet when the herd starts moving.
+    let row = format!(">{}>", ".".repeat(62));
+    let mut packed = PackedHerds::parse(&format!("{}\n{}", row, ".".repeat(64)));
+    assert_eq!(packed.step(), 1);
+    assert_eq!(
+        packed.render(),
+        format!(".>{}>\n{}", ".".repeat(61), ".".repeat(64))
+    );
+
+    let mut packed = PackedHerds::parse("..>\n...\n.v.");
+    assert_eq!(packed.step(), 2);
+    assert_eq!(packed.render(), ">v.\n...\n...");
 }
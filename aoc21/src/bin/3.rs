@@ -1,62 +1,279 @@
+use num::BigUint;
+use std::fmt;
+
 fn main() {
     const INPUT: &str = include_str!("../inputs/3.txt");
     // const INPUT: &str = "00100\n11110\n10110\n10111\n10101\n01111\n00111\n11100\n10000\n11001\n00010\n01010";
+    let report = Report::parse(INPUT).unwrap();
+
+    let gamma = report.gamma(TieBreak::PreferOne).to_biguint();
+    let epsilon = report.epsilon(TieBreak::PreferZero).to_biguint();
+    println!(
+        "bin_len is {}, msb is {}, and lsb is {}. Product is: {}",
+        report.width,
+        gamma,
+        epsilon,
+        &gamma * &epsilon
+    );
+
+    let oxygen_rating = report
+        .rating(Criterion::MostCommon, TieBreak::PreferOne)
+        .unwrap()
+        .to_biguint();
+    let scrubber_rating = report
+        .rating(Criterion::LeastCommon, TieBreak::PreferZero)
+        .unwrap()
+        .to_biguint();
+    println!(
+        "Oxygen generating rating: {}, C02 scrubber rating: {}, life support rating: {}",
+        oxygen_rating,
+        scrubber_rating,
+        &oxygen_rating * &scrubber_rating
+    );
+}
 
-    let bin_len: u32 = INPUT.lines().next().unwrap().chars().count().try_into().unwrap();
-    let vals: Vec<u128> = INPUT.lines().map(|s|isize::from_str_radix(s, 2).unwrap().try_into().unwrap()).collect();
-    let msb: u128 = (0..bin_len).map(|i|most_common_nth::<u128>(&vals, i) << i).sum();
-    let lsb = (msb ^ (2u128.pow(bin_len)-1)) & (2u128.pow(bin_len)-1);
-    println!("bin_len is {}, msb is {}, and lsb is {}. Product is: {}", bin_len, msb, lsb, msb * lsb);
+// Fixed width bit string, where bit 0 is the leftmost (most significant) one.
+// Packed 64 to a word, so any width fits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct BitVec {
+    width: usize,
+    words: Vec<u64>,
+}
 
-    let mut vals2 = vals.clone();
-    for i in (0..bin_len).rev() {
-        let common = most_common_nth::<u128>(&vals2, i) << i;
-        vals2 = vals2.into_iter().filter(|num|(num & (1 << i)) == common).collect();
-        if vals2.is_empty() {
-            panic!("This wasn't supposed to happen.");
-        } else if vals2.len() == 1 {
-            break;
+impl BitVec {
+    fn new(width: usize) -> BitVec {
+        BitVec {
+            width,
+            words: vec![0; width.div_ceil(64)],
         }
     }
 
-    let oxygen_rating = vals2[0];
+    fn parse(s: &str) -> Option<BitVec> {
+        let mut bits = BitVec::new(s.len());
+        for (i, c) in s.chars().enumerate() {
+            match c {
+                '0' => (),
+                '1' => bits.set(i, true),
+                _ => return None,
+            }
+        }
+        Some(bits)
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self.words[i / 64] >> (i % 64) & 1 == 1
+    }
 
-    let mut vals2 = vals.clone();
-    for i in (0..bin_len).rev() {
-        let common = most_common_nth::<u128>(&vals2, i) << i;
-        vals2 = vals2.into_iter().filter(|num|(num & (1 << i)) != common).collect();
-        if vals2.is_empty() {
-            panic!("This wasn't supposed to happen.");
-        } else if vals2.len() == 1 {
-            break;
+    fn set(&mut self, i: usize, bit: bool) {
+        if bit {
+            self.words[i / 64] |= 1 << (i % 64);
+        } else {
+            self.words[i / 64] &= !(1 << (i % 64));
         }
     }
 
-    let scrubber_rating = vals2[0];
+    fn to_biguint(&self) -> BigUint {
+        (0..self.width).fold(BigUint::default(), |n, i| {
+            (n << 1u32) + BigUint::from(self.get(i) as u8)
+        })
+    }
+}
 
-    println!("Oxygen generating rating: {}, C02 scrubber rating: {}, life support rating: {}", oxygen_rating, scrubber_rating, oxygen_rating * scrubber_rating);
+impl fmt::Display for BitVec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for i in 0..self.width {
+            write!(f, "{}", if self.get(i) { '1' } else { '0' })?;
+        }
+        Ok(())
+    }
+}
+
+// Which bit wins when both are equally common
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TieBreak {
+    PreferOne,
+    PreferZero,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Criterion {
+    MostCommon,
+    LeastCommon,
 }
 
-fn most_common_nth<I: std::convert::From<u8>>(nums: &[u128], n: u32) -> I {
-    let count: u128 = nums.iter().count().try_into().unwrap();
-    if 2u128.pow(n) * count / 2 <= nums.iter().map(|i|i & 2u128.pow(n)).sum() {
-        1u8.try_into().unwrap()
-    } else {
-        0u8.try_into().unwrap()
+impl Criterion {
+    fn pick(&self, ones: usize, total: usize, tie: TieBreak) -> bool {
+        let zeros = total - ones;
+        if ones == zeros {
+            return tie == TieBreak::PreferOne;
+        }
+        match self {
+            Criterion::MostCommon => zeros < ones,
+            Criterion::LeastCommon => ones < zeros,
+        }
     }
 }
 
-/*
-fn significant_bit_pos(num: u64) -> u32 {
-    for i in (1u64..64u64).rev() {
-        if 0 < (num >> i) {
-            return u32::try_from(i).unwrap()
+struct Report {
+    // Taken from the first line, which every other line has to match
+    width: usize,
+    numbers: Vec<BitVec>,
+}
+
+impl Report {
+    fn parse(input: &str) -> Result<Report, String> {
+        let lines: Vec<(usize, &str)> = input
+            .lines()
+            .map(|l| l.trim())
+            .enumerate()
+            .filter(|(_, l)| !l.is_empty())
+            .collect();
+        let width = lines.first().map_or(0, |(_, l)| l.len());
+        let numbers = lines
+            .into_iter()
+            .map(|(i, l)| {
+                if l.len() != width {
+                    return Err(format!(
+                        "Line {} is {} bits wide, expected {}",
+                        i + 1,
+                        l.len(),
+                        width
+                    ));
+                }
+                BitVec::parse(l).ok_or(format!("Line {} is not a binary number", i + 1))
+            })
+            .collect::<Result<Vec<BitVec>, String>>()?;
+        Ok(Report { width, numbers })
+    }
+
+    fn ones(numbers: &[&BitVec], bit: usize) -> usize {
+        numbers.iter().filter(|n| n.get(bit)).count()
+    }
+
+    fn common_bits(&self, criterion: Criterion, tie: TieBreak) -> BitVec {
+        let numbers: Vec<&BitVec> = self.numbers.iter().collect();
+        let mut bits = BitVec::new(self.width);
+        for i in 0..self.width {
+            let ones = Report::ones(&numbers, i);
+            bits.set(i, criterion.pick(ones, numbers.len(), tie));
+        }
+        bits
+    }
+
+    fn gamma(&self, tie: TieBreak) -> BitVec {
+        self.common_bits(Criterion::MostCommon, tie)
+    }
+
+    fn epsilon(&self, tie: TieBreak) -> BitVec {
+        self.common_bits(Criterion::LeastCommon, tie)
+    }
+
+    // Keeps only the numbers with the picked bit, one bit at a time from the
+    // left, until a single number remains. A bit that every remaining number
+    // agrees on doesn't filter anything out, even when asked for the least
+    // common one.
+    fn rating(&self, criterion: Criterion, tie: TieBreak) -> Option<BitVec> {
+        let mut numbers: Vec<&BitVec> = self.numbers.iter().collect();
+        for i in 0..self.width {
+            if numbers.len() <= 1 {
+                break;
+            }
+            let bit = criterion.pick(Report::ones(&numbers, i), numbers.len(), tie);
+            let kept: Vec<&BitVec> = numbers
+                .iter()
+                .copied()
+                .filter(|n| n.get(i) == bit)
+                .collect();
+            if !kept.is_empty() {
+                numbers = kept;
+            }
         }
+        numbers.first().map(|n| (*n).clone())
     }
-    0
 }
 
-fn trim_bits(num: u64, bit_len: u32) -> u64 {
-    num & (2u64.pow(bit_len)-1)
+#[cfg(test)]
+const EXAMPLE: &str =
+    "00100\n11110\n10110\n10111\n10101\n01111\n00111\n11100\n10000\n11001\n00010\n01010";
+
+#[test]
+fn test_example() {
+    let report = Report::parse(EXAMPLE).unwrap();
+    assert_eq!(report.width, 5);
+    assert_eq!(report.gamma(TieBreak::PreferOne).to_string(), "10110");
+    assert_eq!(report.epsilon(TieBreak::PreferZero).to_string(), "01001");
+    let oxygen = report
+        .rating(Criterion::MostCommon, TieBreak::PreferOne)
+        .unwrap();
+    let scrubber = report
+        .rating(Criterion::LeastCommon, TieBreak::PreferZero)
+        .unwrap();
+    assert_eq!(oxygen.to_biguint(), BigUint::from(23u32));
+    assert_eq!(scrubber.to_biguint(), BigUint::from(10u32));
+}
+
+#[test]
+fn test_tie_breaks() {
+    let report = Report::parse("01\n10").unwrap();
+    assert_eq!(report.gamma(TieBreak::PreferOne).to_string(), "11");
+    assert_eq!(report.gamma(TieBreak::PreferZero).to_string(), "00");
+    assert_eq!(
+        report
+            .rating(Criterion::MostCommon, TieBreak::PreferOne)
+            .unwrap()
+            .to_string(),
+        "10"
+    );
+    assert_eq!(
+        report
+            .rating(Criterion::MostCommon, TieBreak::PreferZero)
+            .unwrap()
+            .to_string(),
+        "01"
+    );
+
+    // Everything agrees on the first bit, so only the second one decides
+    let report = Report::parse("10\n11\n11").unwrap();
+    assert_eq!(
+        report
+            .rating(Criterion::LeastCommon, TieBreak::PreferZero)
+            .unwrap()
+            .to_string(),
+        "10"
+    );
+}
+
+#[test]
+fn test_wide_numbers() {
+    // The example shifted 200 bits to the left, and padded with zeros
+    let padding = "0".repeat(200);
+    let wide: String = EXAMPLE
+        .lines()
+        .map(|l| format!("{}{}{}\n", padding, l, padding))
+        .collect();
+    let report = Report::parse(&wide).unwrap();
+    assert_eq!(report.width, 405);
+
+    let shift = |n: u32| BigUint::from(n) << 200u32;
+    assert_eq!(report.gamma(TieBreak::PreferOne).to_biguint(), shift(22));
+    let oxygen = report
+        .rating(Criterion::MostCommon, TieBreak::PreferOne)
+        .unwrap();
+    let scrubber = report
+        .rating(Criterion::LeastCommon, TieBreak::PreferZero)
+        .unwrap();
+    assert_eq!(oxygen.to_biguint(), shift(23));
+    assert_eq!(scrubber.to_biguint(), shift(10));
+}
+
+#[test]
+fn test_bad_input() {
+    assert_eq!(
+        Report::parse("0101\n011\n1111").err(),
+        Some("Line 2 is 3 bits wide, expected 4".to_string())
+    );
+    assert_eq!(
+        Report::parse("0101\n0121").err(),
+        Some("Line 2 is not a binary number".to_string())
+    );
 }
-*/
\ No newline at end of file
@@ -1,87 +1,259 @@
-type Board = Vec<Vec<(i32, bool)>>;
+use std::collections::HashMap;
 
 fn main() {
     const INPUT: &str = include_str!("../inputs/4.txt");
     // const INPUT: &str = "7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1\n\n22 13 17 11  0\n 8  2 23  4 24\n21  9 14 16  7\n 6 10  3 18  5\n 1 12 20 15 19\n\n 3 15  0  2 22\n 9 18 13 17  5\n19  8  7 25 23\n20 11 10 24  4\n14 21 16 12  6\n\n14 21 17 24  4\n10 16 15  9 19\n18  8 23 26 20\n22 11 13  6  5\n 2  0 12  3  7";
 
-    let mut boards = INPUT.split("\n\n");
-    let ran_numbers: Vec<i32> = boards
-        .next()
-        .unwrap()
-        .split(',')
-        .map(|s| s.parse::<i32>().unwrap())
-        .collect();
-
-    let mut boards: Vec<Board> = boards
-        .map(|b| {
-            b.lines()
-                .map(|l| {
-                    l.split(' ')
-                        .filter_map(|s| {
-                            if let Ok(num) = s.parse::<i32>() {
-                                Some((num, false))
-                            } else {
-                                None
-                            }
-                        })
-                        .collect()
-                })
-                .collect()
-        })
-        .collect();
-    let board_count = boards.len();
-    let mut skipped_boards = Vec::new();
-
-    for num in ran_numbers {
-        for (i, board) in boards.iter_mut().enumerate() {
-            if skipped_boards.contains(&i) {
-                continue;
-            }
-            'outer: for row in board.iter_mut() {
-                for (j, marked) in row {
-                    if *j == num {
-                        *marked = true;
-                        break 'outer; // This is such a simple and smart feature
-                    }
-                }
+    let (draws, mut game) = parse_input(INPUT, Rules::standard()).unwrap();
+    let wins = game.play(&draws);
+    let board_count = game.boards.len();
+    for (i, win) in wins.iter().enumerate() {
+        println!(
+            "Board {} won{} on draw {} with a score of {}",
+            win.board + 1,
+            if i == 0 {
+                " first"
+            } else if i + 1 == board_count {
+                " last"
+            } else {
+                ""
+            },
+            win.draw,
+            win.score
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Rules {
+    size: usize,
+    // Whether completing one of the two diagonals also wins
+    diagonals: bool,
+}
+
+impl Rules {
+    fn standard() -> Rules {
+        Rules {
+            size: 5,
+            diagonals: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Win {
+    board: usize,
+    draw: u32,
+    score: u64,
+}
+
+struct Board {
+    // Row major
+    numbers: Vec<u32>,
+    marked: Vec<bool>,
+    row_hits: Vec<usize>,
+    column_hits: Vec<usize>,
+    // Main diagonal, then the anti-diagonal
+    diagonal_hits: [usize; 2],
+    unmarked_sum: u64,
+    won: bool,
+}
+
+impl Board {
+    fn new(numbers: Vec<u32>, size: usize) -> Board {
+        Board {
+            marked: vec![false; numbers.len()],
+            row_hits: vec![0; size],
+            column_hits: vec![0; size],
+            diagonal_hits: [0; 2],
+            unmarked_sum: numbers.iter().map(|&n| n as u64).sum(),
+            numbers,
+            won: false,
+        }
+    }
+
+    // Marks a cell, returning whether that completed a line
+    fn mark(&mut self, cell: usize, rules: &Rules) -> bool {
+        if self.marked[cell] {
+            return false;
+        }
+        self.marked[cell] = true;
+        self.unmarked_sum -= self.numbers[cell] as u64;
+
+        let n = rules.size;
+        let (row, column) = (cell / n, cell % n);
+        self.row_hits[row] += 1;
+        self.column_hits[column] += 1;
+        let mut complete = self.row_hits[row] == n || self.column_hits[column] == n;
+        if rules.diagonals {
+            if row == column {
+                self.diagonal_hits[0] += 1;
+                complete |= self.diagonal_hits[0] == n;
             }
-            if has_won(board) {
-                let unmarked_sum: i32 = board
-                    .concat()
-                    .iter()
-                    .filter_map(|(i, marked)| if !marked { Some(i) } else { None })
-                    .sum();
-                println!(
-                    "Board {} won{} with an unmarked sum of {} and a score of {}",
-                    i + 1,
-                    if skipped_boards.is_empty() {
-                        " first"
-                    } else if board_count - 1 == skipped_boards.len() {
-                        " last"
-                    } else {
-                        ""
-                    },
-                    unmarked_sum,
-                    unmarked_sum * num
-                );
-                skipped_boards.push(i);
+            if row + column == n - 1 {
+                self.diagonal_hits[1] += 1;
+                complete |= self.diagonal_hits[1] == n;
             }
         }
+        complete
     }
 }
 
-// https://www.hackertouch.com/matrix-transposition-in-rust.html
-fn matrix_transpose<T: Copy>(m: &Vec<Vec<T>>) -> Vec<Vec<T>> {
-    let mut t = vec![Vec::with_capacity(m.len()); m[0].len()];
-    for r in m {
-        for i in 0..r.len() {
-            t[i].push(r[i]);
+struct BingoGame {
+    rules: Rules,
+    boards: Vec<Board>,
+    // Every (board, cell) a number appears in
+    index: HashMap<u32, Vec<(usize, usize)>>,
+}
+
+impl BingoGame {
+    fn new(rules: Rules, boards: Vec<Vec<u32>>) -> BingoGame {
+        let mut index: HashMap<u32, Vec<(usize, usize)>> = HashMap::new();
+        for (b, numbers) in boards.iter().enumerate() {
+            for (cell, &n) in numbers.iter().enumerate() {
+                index.entry(n).or_default().push((b, cell));
+            }
+        }
+        let boards = boards
+            .into_iter()
+            .map(|numbers| Board::new(numbers, rules.size))
+            .collect();
+        BingoGame {
+            rules,
+            boards,
+            index,
+        }
+    }
+
+    // The boards winning on this draw, in board order. Boards that have
+    // already won keep getting marked, but never win again.
+    fn draw(&mut self, number: u32) -> Vec<Win> {
+        let mut wins = Vec::new();
+        for &(b, cell) in self.index.get(&number).into_iter().flatten() {
+            let board = &mut self.boards[b];
+            if board.mark(cell, &self.rules) && !board.won {
+                board.won = true;
+                wins.push(Win {
+                    board: b,
+                    draw: number,
+                    score: board.unmarked_sum * number as u64,
+                });
+            }
         }
+        wins.sort_by_key(|w| w.board);
+        wins
+    }
+
+    // Every win in the order they happen, stopping once all boards have won
+    fn play(&mut self, draws: &[u32]) -> Vec<Win> {
+        let mut wins = Vec::new();
+        for &number in draws {
+            if wins.len() == self.boards.len() {
+                break;
+            }
+            wins.extend(self.draw(number));
+        }
+        wins
     }
-    t
 }
 
-fn has_won(board: &Board) -> bool {
-    let check_rows = |b: &Board| b.iter().any(|row| row.iter().all(|(_, marked)| *marked));
-    check_rows(board) || check_rows(&matrix_transpose(board))
+// The boards are read as a flat list of numbers, so the layout doesn't matter
+// as long as every board has size * size numbers.
+fn parse_input(input: &str, rules: Rules) -> Option<(Vec<u32>, BingoGame)> {
+    let (draws, boards) = input.split_once("\n\n")?;
+    let draws: Vec<u32> = draws
+        .trim()
+        .split(',')
+        .map(|s| s.parse().ok())
+        .collect::<Option<_>>()?;
+    let numbers: Vec<u32> = boards
+        .split_whitespace()
+        .map(|s| s.parse().ok())
+        .collect::<Option<_>>()?;
+    let cells = rules.size * rules.size;
+    if cells == 0 || !numbers.len().is_multiple_of(cells) {
+        return None;
+    }
+    let boards = numbers.chunks(cells).map(|b| b.to_vec()).collect();
+    Some((draws, BingoGame::new(rules, boards)))
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = "7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1\n\n22 13 17 11  0\n 8  2 23  4 24\n21  9 14 16  7\n 6 10  3 18  5\n 1 12 20 15 19\n\n 3 15  0  2 22\n 9 18 13 17  5\n19  8  7 25 23\n20 11 10 24  4\n14 21 16 12  6\n\n14 21 17 24  4\n10 16 15  9 19\n18  8 23 26 20\n22 11 13  6  5\n 2  0 12  3  7";
+
+#[test]
+fn test_example() {
+    let (draws, mut game) = parse_input(EXAMPLE, Rules::standard()).unwrap();
+    assert_eq!(
+        game.play(&draws),
+        vec![
+            Win {
+                board: 2,
+                draw: 24,
+                score: 4512
+            },
+            Win {
+                board: 0,
+                draw: 16,
+                score: 2192
+            },
+            Win {
+                board: 1,
+                draw: 13,
+                score: 1924
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_diagonals() {
+    let (draws, mut game) = parse_input(
+        "3,5,7,9\n\n1 2 3\n4 5 6\n7 8 9\n\n9 3 2\n4 5 6\n1 8 7",
+        Rules {
+            size: 3,
+            diagonals: true,
+        },
+    )
+    .unwrap();
+    // 3, 5 and 7 complete the anti-diagonal of the first board, and 9 then
+    // completes the main diagonal of the second one
+    assert_eq!(
+        game.play(&draws),
+        vec![
+            Win {
+                board: 0,
+                draw: 7,
+                score: (1 + 2 + 4 + 6 + 8 + 9) * 7
+            },
+            Win {
+                board: 1,
+                draw: 9,
+                score: (2 + 4 + 6 + 1 + 8) * 9
+            },
+        ]
+    );
+
+    let (draws, mut game) = parse_input(
+        "1,5,9\n\n1 2 3\n4 5 6\n7 8 9",
+        Rules {
+            size: 3,
+            diagonals: false,
+        },
+    )
+    .unwrap();
+    assert_eq!(game.play(&draws), vec![]);
+}
+
+#[test]
+fn test_bad_board_size() {
+    assert!(parse_input(EXAMPLE, Rules::standard()).is_some());
+    assert!(parse_input(
+        EXAMPLE,
+        Rules {
+            size: 4,
+            diagonals: false
+        }
+    )
+    .is_none());
 }
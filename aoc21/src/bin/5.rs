@@ -1,68 +1,400 @@
 use std::collections::HashMap;
 
-type Point = (u32, u32);
+type Point = (i64, i64);
 
 fn main() {
     const INPUT: &str = include_str!("../inputs/5.txt");
     // const INPUT: &str = "0,9 -> 5,9\n8,0 -> 0,8\n9,4 -> 3,4\n2,2 -> 2,1\n7,0 -> 7,4\n6,4 -> 2,0\n0,9 -> 2,9\n3,4 -> 1,4\n0,0 -> 8,8\n5,5 -> 8,2";
+    let segments = parse_input(INPUT);
 
-    let lines: Vec<(Point, Point)> = INPUT.lines().filter_map(|s|{
-        let parse_point = |s: &str| -> Option<Point> {
-            if let [l,r,..] = &s.split(',').collect::<Vec<&str>>()[..] {
-                if let (Ok(l), Ok(r)) = (l.parse::<u32>(), r.parse::<u32>()) {
-                    return Some((l, r))
-                }
-            }
-            None
+    let straight: Vec<Segment> = segments
+        .iter()
+        .copied()
+        .filter(Segment::is_axis_aligned)
+        .collect();
+    println!(
+        "Part 1: Number of points where atlest 2 lines overlap: {}",
+        count_overlaps(&straight, 2)
+    );
+
+    let with_diagonals: Vec<Segment> = segments
+        .iter()
+        .copied()
+        .filter(|s| s.is_axis_aligned() || s.is_diagonal())
+        .collect();
+    println!(
+        "Part 2: Number of points where atlest 2 lines overlap (with diagonals): {}",
+        count_overlaps(&with_diagonals, 2)
+    );
+}
+
+fn parse_input(input: &str) -> Vec<Segment> {
+    let parse_point = |s: &str| -> Option<Point> {
+        let (l, r) = s.trim().split_once(',')?;
+        Some((l.parse().ok()?, r.parse().ok()?))
+    };
+    input
+        .lines()
+        .filter_map(|s| {
+            let (a, b) = s.split_once(" -> ")?;
+            Some(Segment {
+                a: parse_point(a)?,
+                b: parse_point(b)?,
+            })
+        })
+        .collect()
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Segment {
+    a: Point,
+    b: Point,
+}
+
+impl Segment {
+    fn is_axis_aligned(&self) -> bool {
+        self.a.0 == self.b.0 || self.a.1 == self.b.1
+    }
+
+    fn is_diagonal(&self) -> bool {
+        (self.b.0 - self.a.0).abs() == (self.b.1 - self.a.1).abs()
+    }
+
+    // Every point with integer coordinates on the segment, from a to b. The
+    // smallest step between two of them is the direction divided by the gcd
+    // of its components.
+    fn points(&self) -> impl Iterator<Item = Point> {
+        let (dx, dy) = (self.b.0 - self.a.0, self.b.1 - self.a.1);
+        let steps = gcd(dx, dy);
+        let (sx, sy) = if steps == 0 {
+            (0, 0)
+        } else {
+            (dx / steps, dy / steps)
         };
-        if let [a,b,..] = &s.split(" -> ").collect::<Vec<&str>>()[..] {
-            if let (Some(a), Some(b)) = (parse_point(a), parse_point(b)) {
-                return Some((a, b))
-            }
+        let a = self.a;
+        (0..=steps).map(move |i| (a.0 + i * sx, a.1 + i * sy))
+    }
+}
+
+// How many segments cover each point
+trait Coverage {
+    fn add(&mut self, p: Point);
+
+    fn count_at_least(&self, k: u32) -> usize;
+
+    fn cover(&mut self, segments: &[Segment]) {
+        for p in segments.iter().flat_map(|s| s.points()) {
+            self.add(p);
         }
-        None
-    }).collect();
-
-    let mut points: HashMap<Point, usize> = HashMap::new();
-    for line in &lines {
-        for p in make_line(line.0, line.1, false) {
-            let p_count = points.entry(p).or_insert(0);
-            *p_count += 1;
+    }
+}
+
+// Counts in a flat array spanning the bounding box of the segments
+struct DenseCoverage {
+    min: Point,
+    width: usize,
+    counts: Vec<u32>,
+}
+
+impl DenseCoverage {
+    // Anything larger than this is better off swept
+    const MAX_CELLS: i64 = 1 << 26;
+
+    fn new(segments: &[Segment]) -> Option<DenseCoverage> {
+        let points = segments.iter().flat_map(|s| [s.a, s.b]);
+        let min = points.clone().reduce(|l, r| (l.0.min(r.0), l.1.min(r.1)))?;
+        let max = points.reduce(|l, r| (l.0.max(r.0), l.1.max(r.1)))?;
+        let (width, height) = (max.0 - min.0 + 1, max.1 - min.1 + 1);
+        if Self::MAX_CELLS < width.checked_mul(height)? {
+            return None;
         }
+        Some(DenseCoverage {
+            min,
+            width: width as usize,
+            counts: vec![0; (width * height) as usize],
+        })
     }
+}
+
+impl Coverage for DenseCoverage {
+    fn add(&mut self, (x, y): Point) {
+        let (x, y) = ((x - self.min.0) as usize, (y - self.min.1) as usize);
+        self.counts[y * self.width + x] += 1;
+    }
+
+    fn count_at_least(&self, k: u32) -> usize {
+        self.counts.iter().filter(|&&n| k <= n).count()
+    }
+}
+
+// One entry per point, which is simple but only cheap for short segments.
+// Here to check the others against.
+#[cfg(test)]
+#[derive(Default)]
+struct SparseCoverage {
+    counts: HashMap<Point, u32>,
+}
+
+#[cfg(test)]
+impl Coverage for SparseCoverage {
+    fn add(&mut self, p: Point) {
+        *self.counts.entry(p).or_insert(0) += 1;
+    }
+
+    fn count_at_least(&self, k: u32) -> usize {
+        self.counts.values().filter(|&&n| k <= n).count()
+    }
+}
+
+// The line through a segment, as the smallest step between lattice points on
+// it (pointing right, or up if vertical) and the value of sy * x - sx * y,
+// which is the same for every point on the line.
+type Line = (i128, i128, i128);
 
-    println!("Part 1: Number of points where atlest 2 lines overlap: {}", points.iter().filter(|(_, n)|1 < **n).count());
+// Lattice points on a line, numbered along it
+#[derive(Default)]
+struct LineCoverage {
+    // (first, last, count) for every stretch that is covered at all, in order
+    pieces: Vec<(i128, i128, u32)>,
+}
 
+impl LineCoverage {
+    // Sweeps along the line, keeping count of how many segments cover it
+    fn new(intervals: &[(i128, i128)]) -> LineCoverage {
+        let mut events: Vec<(i128, i64)> = intervals
+            .iter()
+            .flat_map(|&(first, last)| [(first, 1), (last + 1, -1)])
+            .collect();
+        events.sort_unstable();
+        let mut pieces = Vec::new();
+        let mut count = 0i64;
+        for (i, &(at, change)) in events.iter().enumerate() {
+            count += change;
+            if let Some(&(next, _)) = events.get(i + 1) {
+                if at < next && 0 < count {
+                    pieces.push((at, next - 1, count as u32));
+                }
+            }
+        }
+        LineCoverage { pieces }
+    }
 
-    let mut points: HashMap<Point, usize> = HashMap::new();
-    for line in &lines {
-        for p in make_line(line.0, line.1, true) {
-            let p_count = points.entry(p).or_insert(0);
-            *p_count += 1;
+    fn count_at(&self, u: i128) -> u32 {
+        let i = self.pieces.partition_point(|&(_, last, _)| last < u);
+        match self.pieces.get(i) {
+            Some(&(first, _, count)) if first <= u => count,
+            _ => 0,
         }
     }
 
-    println!("Part 2: Number of points where atlest 2 lines overlap (with diagonals): {}", points.iter().filter(|(_, n)|1 < **n).count());
+    fn count_at_least(&self, k: u32) -> i128 {
+        self.pieces
+            .iter()
+            .filter(|&&(_, _, count)| k <= count)
+            .map(|&(first, last, _)| last - first + 1)
+            .sum()
+    }
+}
+
+fn line_of(segment: &Segment) -> Option<Line> {
+    let (dx, dy) = (segment.b.0 - segment.a.0, segment.b.1 - segment.a.1);
+    let steps = gcd(dx, dy);
+    if steps == 0 {
+        return None;
+    }
+    let (mut sx, mut sy) = (i128::from(dx / steps), i128::from(dy / steps));
+    if sx < 0 || (sx == 0 && sy < 0) {
+        (sx, sy) = (-sx, -sy);
+    }
+    let (x, y) = (i128::from(segment.a.0), i128::from(segment.a.1));
+    Some((sx, sy, sy * x - sx * y))
+}
+
+// Where a lattice point is along the line. Neighbouring lattice points differ
+// by sx^2 + sy^2 in their dot product with the step.
+fn position_on((sx, sy, _): Line, (x, y): (i128, i128)) -> i128 {
+    (x * sx + y * sy).div_euclid(sx * sx + sy * sy)
 }
 
-fn make_line(a: Point, b: Point, diagonals: bool) -> Vec<Point> {
-    let mut line = Vec::new();
-    let make_range = |first,last| -> Vec<u32> {if first <= last { (first..=last).collect() } else { (last..=first).rev().collect() }};
+// The lattice point where two lines cross, if any
+fn crossing((sx1, sy1, c1): Line, (sx2, sy2, c2): Line) -> Option<(i128, i128)> {
+    let det = sx1 * sy2 - sy1 * sx2;
+    if det == 0 {
+        return None;
+    }
+    let (x, y) = (sx1 * c2 - sx2 * c1, sy1 * c2 - sy2 * c1);
+    if x % det != 0 || y % det != 0 {
+        return None;
+    }
+    Some((x / det, y / det))
+}
 
-    if a.0 == b.0 || a.1 == b.1 {
-        if a.0 != b.0 {
-            for i in make_range(a.0, b.0) {
-                line.push((i, a.1))
+// Counts without visiting every point, so segments can be as long as they
+// like. Segments on the same line are swept along it together, which gets
+// every point right except where lines cross. Those points are then looked at
+// one by one, as are segments that are a single point.
+fn sweep_overlaps(segments: &[Segment], k: u32) -> usize {
+    let mut intervals: HashMap<Line, Vec<(i128, i128)>> = HashMap::new();
+    let mut single_points: HashMap<(i128, i128), u32> = HashMap::new();
+    for segment in segments {
+        let a = (i128::from(segment.a.0), i128::from(segment.a.1));
+        let b = (i128::from(segment.b.0), i128::from(segment.b.1));
+        match line_of(segment) {
+            Some(line) => {
+                let (u, v) = (position_on(line, a), position_on(line, b));
+                intervals
+                    .entry(line)
+                    .or_default()
+                    .push((u.min(v), u.max(v)));
             }
-        } else {
-            for i in make_range(a.1, b.1) {
-                line.push((a.0, i))
+            None => *single_points.entry(a).or_insert(0) += 1,
+        }
+    }
+    let lines: Vec<(Line, LineCoverage)> = intervals
+        .into_iter()
+        .map(|(line, intervals)| (line, LineCoverage::new(&intervals)))
+        .collect();
+    let covered = |i: usize, p: (i128, i128)| lines[i].1.count_at(position_on(lines[i].0, p));
+
+    // The lines that actually cover each point where they cross
+    let mut crossings: HashMap<(i128, i128), Vec<usize>> = HashMap::new();
+    for i in 0..lines.len() {
+        for j in i + 1..lines.len() {
+            if let Some(p) = crossing(lines[i].0, lines[j].0) {
+                if 0 < covered(i, p) && 0 < covered(j, p) {
+                    crossings.entry(p).or_default().extend([i, j]);
+                }
             }
         }
-    } else if diagonals {
-        for (i, j) in (make_range(a.0,b.0).into_iter()).zip(make_range(a.1, b.1)) {
-            line.push((i, j))
+    }
+    for &p @ (x, y) in single_points.keys() {
+        let through = (0..lines.len()).filter(|&i| {
+            let (sx, sy, c) = lines[i].0;
+            sy * x - sx * y == c && 0 < covered(i, p)
+        });
+        crossings.entry(p).or_default().extend(through);
+    }
+
+    let mut total: i128 = lines.iter().map(|(_, c)| c.count_at_least(k)).sum();
+    for (p, mut through) in crossings {
+        through.sort_unstable();
+        through.dedup();
+        let counts: Vec<u32> = through.iter().map(|&i| covered(i, p)).collect();
+        // Each line counted this point on its own, but it's only one point
+        total -= counts.iter().filter(|&&n| k <= n).count() as i128;
+        let count = counts.iter().sum::<u32>() + single_points.get(&p).unwrap_or(&0);
+        if k <= count {
+            total += 1;
+        }
+    }
+    total as usize
+}
+
+// Points covered by at least k of the segments, in a dense array when the
+// segments fit in a reasonably small area, and by sweeping otherwise.
+fn count_overlaps(segments: &[Segment], k: u32) -> usize {
+    match DenseCoverage::new(segments) {
+        Some(mut dense) => {
+            dense.cover(segments);
+            dense.count_at_least(k)
         }
+        None => sweep_overlaps(segments, k),
     }
-    line
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = "0,9 -> 5,9\n8,0 -> 0,8\n9,4 -> 3,4\n2,2 -> 2,1\n7,0 -> 7,4\n6,4 -> 2,0\n0,9 -> 2,9\n3,4 -> 1,4\n0,0 -> 8,8\n5,5 -> 8,2";
+
+#[test]
+fn test_example() {
+    let segments = parse_input(EXAMPLE);
+    let straight: Vec<Segment> = segments
+        .iter()
+        .copied()
+        .filter(Segment::is_axis_aligned)
+        .collect();
+    assert_eq!(count_overlaps(&straight, 2), 5);
+    assert_eq!(count_overlaps(&segments, 2), 12);
+    assert_eq!(count_overlaps(&segments, 3), 2);
+}
+
+#[test]
+fn test_lattice_points() {
+    let segment = |a, b| Segment { a, b };
+    assert_eq!(
+        segment((0, 0), (6, 4)).points().collect::<Vec<Point>>(),
+        vec![(0, 0), (3, 2), (6, 4)]
+    );
+    assert_eq!(
+        segment((5, -1), (-1, 1)).points().collect::<Vec<Point>>(),
+        vec![(5, -1), (2, 0), (-1, 1)]
+    );
+    assert_eq!(
+        segment((2, 3), (7, 5)).points().collect::<Vec<Point>>(),
+        vec![(2, 3), (7, 5)]
+    );
+    assert_eq!(
+        segment((4, 4), (4, 4)).points().collect::<Vec<Point>>(),
+        vec![(4, 4)]
+    );
+}
+
+#[test]
+fn test_arbitrary_angles() {
+    let segments = parse_input("0,0 -> 6,4\n0,2 -> 6,2\n3,0 -> 3,6\n-3,8 -> 9,-4");
+    // Only the first one skips lattice points, but all of them meet in (3, 2)
+    assert_eq!(count_overlaps(&segments, 4), 1);
+    assert_eq!(count_overlaps(&segments, 2), 1);
+    assert_eq!(count_overlaps(&segments, 1), 3 + 7 + 7 + 13 - 3);
+}
+
+#[test]
+fn test_backends_agree() {
+    let input = parse_input(include_str!("../inputs/5.txt"));
+    let odd = parse_input("0,0 -> 6,4\n0,2 -> 6,2\n3,0 -> 3,6\n-3,8 -> 9,-4\n3,2 -> 3,2");
+    // Overlapping collinear segments, some of them just touching at the ends
+    let collinear = parse_input(
+        "0,0 -> 10,10\n5,5 -> 20,20\n20,20 -> 30,30\n8,8 -> 8,8\n-4,-2 -> 8,4\n2,1 -> 10,5\n0,8 -> 8,0",
+    );
+    for segments in [input, odd, collinear, parse_input(EXAMPLE)] {
+        let mut dense = DenseCoverage::new(&segments).unwrap();
+        let mut sparse = SparseCoverage::default();
+        dense.cover(&segments);
+        sparse.cover(&segments);
+        for k in 1..5 {
+            assert_eq!(dense.count_at_least(k), sparse.count_at_least(k));
+            assert_eq!(sweep_overlaps(&segments, k), sparse.count_at_least(k));
+        }
+    }
+}
+
+#[test]
+fn test_huge_coordinates() {
+    // Far too spread out for a dense array, or for visiting every point
+    let huge = parse_input("0,0 -> 3000000000,3000000000\n0,3000000000 -> 3000000000,0");
+    assert!(DenseCoverage::new(&huge).is_none());
+    assert_eq!(count_overlaps(&huge, 1), 2 * 3000000001 - 1);
+    assert_eq!(count_overlaps(&huge, 2), 1);
+
+    let far = parse_input(
+        "1000000000000,0 -> 1000000000010,0\n1000000000005,-3 -> 1000000000005,3\n0,0 -> 0,1",
+    );
+    assert!(DenseCoverage::new(&far).is_none());
+    assert_eq!(count_overlaps(&far, 2), 1);
+    assert_eq!(count_overlaps(&far, 1), 11 + 7 - 1 + 2);
+
+    // Long collinear overlaps that cross another line twice over
+    let stacked = parse_input(
+        "0,0 -> 4000000000,0\n1000000000,0 -> 5000000000,0\n2000000000,-7 -> 2000000000,7",
+    );
+    assert_eq!(count_overlaps(&stacked, 2), 3000000001);
+    assert_eq!(count_overlaps(&stacked, 3), 1);
+}
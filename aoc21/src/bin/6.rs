@@ -1,40 +1,177 @@
-#[derive(Debug)]
-struct Fish {
-    birth_timer: i32,
-}
+use num::{BigUint, One, Zero};
+
+// Fish are only distinguished by their timer, so count how many fish have
+// each timer value instead of keeping track of every single fish.
+type Counts = [u64; 9];
+type Matrix = [[BigUint; 9]; 9];
 
-impl Fish {
-    fn new() -> Self {
-        Fish { birth_timer: 8 }
+fn parse_input(input: &str) -> Result<Counts, String> {
+    let mut counts = [0; 9];
+    for s in input.trim().split(',') {
+        match s.trim().parse::<usize>() {
+            Ok(timer) if timer < counts.len() => counts[timer] += 1,
+            _ => return Err(format!("'{}' is not a timer between 0 and 8", s)),
+        }
     }
+    Ok(counts)
 }
 
 fn main() {
-    // const INPUT: &str = include_str!("../inputs/6.txt");
-    const INPUT: &str = "3,4,3,1,2";
+    const INPUT: &str = include_str!("../inputs/6.txt");
+    let counts = match parse_input(INPUT) {
+        Ok(counts) => counts,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let mut fishies: Vec<Fish> = INPUT
-        .trim()
-        .split(',')
-        .map(|s| Fish { birth_timer: s.parse().unwrap() }).collect();
-    // println!("Initial fishies {:?}", fishies);
-    simulate(&mut fishies, 80);
-    println!("Fish count after 80 days is {}", fishies.len());
+    println!(
+        "Fish count after 80 days is {}",
+        count_fish(&counts, 80, None)
+    );
+    println!(
+        "Fish count after 256 days is {}",
+        count_fish(&counts, 256, None)
+    );
+
+    const MODULUS: u64 = 1_000_000_007;
+    println!(
+        "Fish count after 10^18 days is {} (mod {})",
+        count_fish(&counts, 1_000_000_000_000_000_000, Some(MODULUS)),
+        MODULUS
+    );
 }
 
-fn simulate(fishies: &mut Vec<Fish>, days: u32) {
+// Straightforward day by day version, to check the matrix one against
+#[cfg(test)]
+fn simulate(counts: &Counts, days: u32) -> Counts {
+    let mut counts = *counts;
     for _ in 0..days {
-        let mut new_fish_count = 0;
-        for fish in &mut *fishies {
-            fish.birth_timer -= 1;
-            if fish.birth_timer < 0 {
-                new_fish_count += 1;
-                fish.birth_timer = 6;
-            }
-        }
+        counts.rotate_left(1);
+        // The fish that gave birth restart at 6, their children start at 8
+        counts[6] += counts[8];
+    }
+    counts
+}
 
-        for _ in 0..new_fish_count {
-            fishies.push(Fish::new());
+// One day as a matrix, so that new[i] = sum of M[i][j] * old[j]
+fn transition() -> Matrix {
+    let mut m: Matrix = Default::default();
+    for timer in 1..9 {
+        m[timer - 1][timer] = BigUint::one();
+    }
+    m[6][0] = BigUint::one();
+    m[8][0] = BigUint::one();
+    m
+}
+
+fn identity() -> Matrix {
+    let mut m: Matrix = Default::default();
+    for (i, row) in m.iter_mut().enumerate() {
+        row[i] = BigUint::one();
+    }
+    m
+}
+
+fn reduce(n: BigUint, modulus: Option<u64>) -> BigUint {
+    match modulus {
+        Some(m) => n % m,
+        None => n,
+    }
+}
+
+fn mat_mul(a: &Matrix, b: &Matrix, modulus: Option<u64>) -> Matrix {
+    std::array::from_fn(|i| {
+        std::array::from_fn(|j| {
+            let sum = (0..9).fold(BigUint::zero(), |sum, k| sum + &a[i][k] * &b[k][j]);
+            reduce(sum, modulus)
+        })
+    })
+}
+
+fn mat_pow(m: &Matrix, mut exponent: u64, modulus: Option<u64>) -> Matrix {
+    let mut result = identity();
+    let mut base = m.clone();
+    while 0 < exponent {
+        if exponent & 1 == 1 {
+            result = mat_mul(&result, &base, modulus);
         }
+        base = mat_mul(&base, &base, modulus);
+        exponent >>= 1;
     }
+    result
+}
+
+// Total fish after the given amount of days, optionally modulo a (prime)
+// number for day counts where the exact answer wouldn't fit in memory.
+fn count_fish(counts: &Counts, days: u64, modulus: Option<u64>) -> BigUint {
+    let m = mat_pow(&transition(), days, modulus);
+    let total = m
+        .iter()
+        .flat_map(|row| row.iter().zip(counts))
+        .fold(BigUint::zero(), |sum, (a, n)| sum + a * n);
+    reduce(total, modulus)
+}
+
+#[test]
+fn test_example() {
+    let counts = parse_input("3,4,3,1,2").unwrap();
+    assert_eq!(simulate(&counts, 18).iter().sum::<u64>(), 26);
+    assert_eq!(count_fish(&counts, 18, None), BigUint::from(26u32));
+    assert_eq!(count_fish(&counts, 80, None), BigUint::from(5934u32));
+    assert_eq!(
+        count_fish(&counts, 256, None),
+        BigUint::from(26984457539u64)
+    );
+}
+
+#[test]
+fn test_matrix_matches_simulation() {
+    let counts = parse_input(include_str!("../inputs/6.txt")).unwrap();
+    for days in [0, 1, 7, 9, 100, 300] {
+        let simulated = simulate(&counts, days).iter().sum::<u64>();
+        assert_eq!(
+            count_fish(&counts, days as u64, None),
+            BigUint::from(simulated)
+        );
+        assert_eq!(
+            count_fish(&counts, days as u64, Some(1_000_000_007)),
+            BigUint::from(simulated % 1_000_000_007)
+        );
+    }
+}
+
+#[test]
+fn test_huge_day_count() {
+    // Adding the same amount of days in one go or in two steps must agree
+    let counts = parse_input("3,4,3,1,2").unwrap();
+    const MODULUS: u64 = 998_244_353;
+    let days = 1_000_000_000_000_000_000;
+    let once = count_fish(&counts, days, Some(MODULUS));
+
+    let halfway = mat_pow(&transition(), days / 2, Some(MODULUS));
+    let twice = mat_mul(&halfway, &halfway, Some(MODULUS));
+    let total = twice
+        .iter()
+        .flat_map(|row| row.iter().zip(&counts))
+        .fold(BigUint::zero(), |sum, (a, n)| sum + a * n)
+        % MODULUS;
+    assert_eq!(once, total);
+}
+
+#[test]
+fn test_bad_input() {
+    assert_eq!(
+        parse_input("3,9"),
+        Err(String::from("'9' is not a timer between 0 and 8"))
+    );
+    assert_eq!(
+        parse_input("3,x,4"),
+        Err(String::from("'x' is not a timer between 0 and 8"))
+    );
+    assert_eq!(
+        parse_input("3,,4"),
+        Err(String::from("'' is not a timer between 0 and 8"))
+    );
 }
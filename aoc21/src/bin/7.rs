@@ -1,36 +1,304 @@
 fn main() {
     // const INPUT: &str = "16,1,2,0,4,2,7,1,2,14";
     const INPUT: &str = include_str!("../inputs/7.txt");
-    let mut positions: Vec<i64> = INPUT.trim().split(",").filter_map(|s|s.parse().ok()).collect();
-    // println!("Positions: {:?}", positions);
-    
-    let (mut best_pos, mut best_cost) = (0, i64::MAX);
-    positions.sort_unstable();
-    let (min_pos, max_pos) = (*positions.get(0).unwrap(), *positions.get(positions.len() - 1).unwrap());
-    for center in min_pos..=max_pos {
-        let sum_fuel = positions.iter().map(|p|(p - center).abs()).sum();
-        if sum_fuel < best_cost {
-            best_cost = sum_fuel;
-            best_pos = center;
+    let crabs = parse_input(INPUT);
+
+    // Checks every position instead, for when the fast paths are in doubt
+    let args: Vec<String> = std::env::args().collect();
+    let exhaustive_mode = args.iter().any(|a| a == "--exhaustive");
+    let optimise = |cost: &dyn FuelCost| {
+        if exhaustive_mode {
+            exhaustive(cost, &crabs)
+        } else {
+            cost.optimise(&crabs)
         }
+        .unwrap()
+    };
+
+    let best = optimise(&Linear);
+    println!(
+        "Best linear pos was {}, which will cost {} fuel",
+        best.positions[0], best.cost
+    );
+
+    let best = optimise(&Triangular);
+    println!(
+        "Best pos was {}, which will cost {} fuel",
+        best.positions[0], best.cost
+    );
+
+    // --polynomial c0,c1,c2,... for a custom cost, optionally with
+    // --weights w0,w1,... for every crab
+    let option = |name: &str| -> Option<Vec<i64>> {
+        let i = args.iter().position(|a| a == name)?;
+        Some(parse_input(args.get(i + 1)?))
+    };
+    if let Some(coefficients) = option("--polynomial") {
+        let polynomial = Polynomial { coefficients };
+        let best = match option("--weights") {
+            Some(weights) => optimise(&Weighted {
+                cost: polynomial,
+                weights,
+            }),
+            None => optimise(&polynomial),
+        };
+        println!(
+            "Best pos(es) with the custom cost were {:?}, which will cost {} fuel",
+            best.positions, best.cost
+        );
+    }
+}
+
+fn parse_input(input: &str) -> Vec<i64> {
+    input
+        .trim()
+        .split(',')
+        .filter_map(|s| s.parse().ok())
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+struct Alignment {
+    // Every position that is as cheap as possible, in order
+    positions: Vec<i64>,
+    cost: i64,
+}
+
+// The fuel a crab uses to move a distance. Costs must never decrease with
+// distance and be convex, which makes the total cost convex in the target
+// position as well, so that a ternary search finds the cheapest one.
+trait FuelCost {
+    fn cost(&self, distance: i64) -> i64;
+
+    fn total(&self, crabs: &[i64], target: i64) -> i64 {
+        crabs.iter().map(|p| self.cost((p - target).abs())).sum()
+    }
+
+    fn optimise(&self, crabs: &[i64]) -> Option<Alignment> {
+        ternary_search(self, crabs)
     }
+}
 
-    println!("Best linear pos was {}, which will cost {} fuel", best_pos, best_cost);
-
-    let (mut best_pos, mut best_cost) = (0, i64::MAX);
-    positions.sort_unstable();
-    let (min_pos, max_pos) = (*positions.get(0).unwrap(), *positions.get(positions.len() - 1).unwrap());
-    for center in min_pos..=max_pos {
-        let sum_fuel = positions.iter().map(|p|{
-            let n = (p - center).abs();
-            // arithmetic sum:
-            n * (1+n) / 2
-        }).sum();
-        if sum_fuel < best_cost {
-            best_cost = sum_fuel;
-            best_pos = center;
+// Fuel is never saved by moving past the outermost crabs
+fn bounds(crabs: &[i64]) -> Option<(i64, i64)> {
+    Some((*crabs.iter().min()?, *crabs.iter().max()?))
+}
+
+// Widens a cheapest position to the whole (convex, so contiguous) range of
+// positions that cost the same
+fn plateau<C: FuelCost + ?Sized>(cost: &C, crabs: &[i64], best: i64) -> Alignment {
+    let (min, max) = bounds(crabs).unwrap();
+    let fuel = cost.total(crabs, best);
+    let mut lo = best;
+    while min < lo && cost.total(crabs, lo - 1) == fuel {
+        lo -= 1;
+    }
+    let mut hi = best;
+    while hi < max && cost.total(crabs, hi + 1) == fuel {
+        hi += 1;
+    }
+    Alignment {
+        positions: (lo..=hi).collect(),
+        cost: fuel,
+    }
+}
+
+fn ternary_search<C: FuelCost + ?Sized>(cost: &C, crabs: &[i64]) -> Option<Alignment> {
+    let (mut lo, mut hi) = bounds(crabs)?;
+    while 2 < hi - lo {
+        let (m1, m2) = (lo + (hi - lo) / 3, hi - (hi - lo) / 3);
+        let (f1, f2) = (cost.total(crabs, m1), cost.total(crabs, m2));
+        if f1 < f2 {
+            hi = m2 - 1;
+        } else if f2 < f1 {
+            lo = m1 + 1;
+        } else {
+            (lo, hi) = (m1, m2);
         }
     }
+    let best = (lo..=hi).min_by_key(|&x| cost.total(crabs, x))?;
+    Some(plateau(cost, crabs, best))
+}
+
+// Tries every position, for checking the faster ways against
+fn exhaustive<C: FuelCost + ?Sized>(cost: &C, crabs: &[i64]) -> Option<Alignment> {
+    let (min, max) = bounds(crabs)?;
+    let fuel = (min..=max).map(|x| cost.total(crabs, x)).min()?;
+    Some(Alignment {
+        positions: (min..=max)
+            .filter(|&x| cost.total(crabs, x) == fuel)
+            .collect(),
+        cost: fuel,
+    })
+}
+
+struct Linear;
+
+impl FuelCost for Linear {
+    fn cost(&self, distance: i64) -> i64 {
+        distance
+    }
+
+    // Any position between the two middle crabs is the best
+    fn optimise(&self, crabs: &[i64]) -> Option<Alignment> {
+        let mut sorted = crabs.to_vec();
+        sorted.sort_unstable();
+        let n = sorted.len();
+        let (lo, hi) = (*sorted.get((n.max(1) - 1) / 2)?, sorted[n / 2]);
+        Some(Alignment {
+            positions: (lo..=hi).collect(),
+            cost: self.total(crabs, lo),
+        })
+    }
+}
+
+// Each step costs one more than the previous one
+struct Triangular;
+
+impl FuelCost for Triangular {
+    fn cost(&self, distance: i64) -> i64 {
+        distance * (distance + 1) / 2
+    }
+
+    // The total is half of the sum of squares plus the linear cost, so the
+    // best position is within half a step of the mean.
+    fn optimise(&self, crabs: &[i64]) -> Option<Alignment> {
+        let n = crabs.len() as i64;
+        let sum: i64 = crabs.iter().sum();
+        let (min, max) = bounds(crabs)?;
+        let mean = sum.div_euclid(n);
+        let best = (mean - 1..=mean + 2)
+            .map(|x| x.clamp(min, max))
+            .min_by_key(|&x| self.total(crabs, x))?;
+        Some(plateau(self, crabs, best))
+    }
+}
+
+// Sum of coefficients[i] * distance^i. Non-negative coefficients keep it convex.
+struct Polynomial {
+    coefficients: Vec<i64>,
+}
+
+impl FuelCost for Polynomial {
+    fn cost(&self, distance: i64) -> i64 {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(0, |sum, c| sum * distance + c)
+    }
+}
+
+// Crabs with heavier submarines use more fuel, weights[i] times the cost for
+// crab i. Crabs without a weight count once.
+struct Weighted<C: FuelCost> {
+    cost: C,
+    weights: Vec<i64>,
+}
+
+impl<C: FuelCost> FuelCost for Weighted<C> {
+    fn cost(&self, distance: i64) -> i64 {
+        self.cost.cost(distance)
+    }
+
+    fn total(&self, crabs: &[i64], target: i64) -> i64 {
+        crabs
+            .iter()
+            .enumerate()
+            .map(|(i, p)| self.weights.get(i).unwrap_or(&1) * self.cost.cost((p - target).abs()))
+            .sum()
+    }
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = "16,1,2,0,4,2,7,1,2,14";
+
+#[test]
+fn test_example() {
+    let crabs = parse_input(EXAMPLE);
+    assert_eq!(
+        Linear.optimise(&crabs),
+        Some(Alignment {
+            positions: vec![2],
+            cost: 37
+        })
+    );
+    assert_eq!(
+        Triangular.optimise(&crabs),
+        Some(Alignment {
+            positions: vec![5],
+            cost: 168
+        })
+    );
+    assert_eq!(Linear.optimise(&[]), None);
+}
+
+#[test]
+fn test_ties() {
+    // Anywhere between the two middle crabs costs the same
+    assert_eq!(
+        Linear.optimise(&[0, 1, 5, 9]),
+        Some(Alignment {
+            positions: vec![1, 2, 3, 4, 5],
+            cost: 13
+        })
+    );
+    assert_eq!(
+        Triangular.optimise(&[0, 1]),
+        Some(Alignment {
+            positions: vec![0, 1],
+            cost: 1
+        })
+    );
+}
+
+#[test]
+fn test_fast_paths_match_exhaustive() {
+    let input = parse_input(include_str!("../inputs/7.txt"));
+    let mut crabs_sets = vec![
+        parse_input(EXAMPLE),
+        input[..1].to_vec(),
+        input[..2].to_vec(),
+        input[..7].to_vec(),
+        input[..100].to_vec(),
+        vec![-5, -3, 8, 8, 8, 20],
+        vec![3, 3, 3],
+    ];
+    crabs_sets.push(input);
+
+    for crabs in &crabs_sets {
+        assert_eq!(Linear.optimise(crabs), exhaustive(&Linear, crabs));
+        assert_eq!(ternary_search(&Linear, crabs), exhaustive(&Linear, crabs));
+        assert_eq!(Triangular.optimise(crabs), exhaustive(&Triangular, crabs));
+        assert_eq!(
+            ternary_search(&Triangular, crabs),
+            exhaustive(&Triangular, crabs)
+        );
+
+        let cubic = Polynomial {
+            coefficients: vec![0, 2, 0, 1],
+        };
+        assert_eq!(cubic.optimise(crabs), exhaustive(&cubic, crabs));
+
+        let weighted = Weighted {
+            cost: Triangular,
+            weights: (0..crabs.len() as i64).map(|i| i % 4 + 1).collect(),
+        };
+        assert_eq!(weighted.optimise(crabs), exhaustive(&weighted, crabs));
+    }
+}
 
-    println!("Best pos was {}, which will cost {} fuel", best_pos, best_cost);
-}
\ No newline at end of file
+#[test]
+fn test_polynomial() {
+    let triangular = Polynomial {
+        coefficients: vec![0, 1, 1],
+    };
+    // Twice the triangular cost
+    let crabs = parse_input(EXAMPLE);
+    assert_eq!(
+        triangular.optimise(&crabs),
+        Some(Alignment {
+            positions: vec![5],
+            cost: 2 * 168
+        })
+    );
+}
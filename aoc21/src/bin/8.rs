@@ -1,122 +1,363 @@
-use std::collections::HashSet;
-
 fn main() {
     const INPUT: &str = include_str!("../inputs/8.txt");
-    //     const INPUT: &str = "
-    // be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe
-    // edbfga begcd cbg gc gcadebf fbgde acbgfd abcde gfcbed gfec | fcgedb cgb dgebacf gc
-    // fgaebd cg bdaec gdafb agbcfd gdcbef bgcad gfac gcb cdgabef | cg cg fdcagb cbg
-    // fbegcd cbd adcefb dageb afcb bc aefdc ecdab fgdeca fcdbega | efabcd cedba gadfec cb
-    // aecbfdg fbg gf bafeg dbefa fcge gcbea fcaegb dgceab fcbdga | gecf egdcabf bgf bfgea
-    // fgeab ca afcebg bdacfeg cfaedg gcfdb baec bfadeg bafgc acf | gebdcfa ecba ca fadegcb
-    // dbcfg fgd bdegcaf fgec aegbdf ecdfab fbedc dacgb gdcebf gf | cefg dcbef fcge gbcadfe
-    // bdfegc cbegaf gecbf dfcage bdacg ed bedf ced adcbefg gebcd | ed bcgafe cdgba cbgef
-    // egadfb cdbfeg cegd fecab cgb gbdefca cg fgcdab egfdb bfceg | gbdfcae bgc cg cgb
-    // gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce";
-
-    let displays: Vec<(&str, &str)> = INPUT
-        .lines()
-        .filter_map(|s| {
-            if let [a, b, ..] = s.split(" | ").collect::<Vec<&str>>()[..] {
-                return Some((a, b));
-            }
-            None
-        })
-        .collect();
+    let font = Font::standard();
+    let displays = parse_input(INPUT);
 
-    let smol_len = |s: &str| [2, 4, 3, 7].into_iter().any(|n| n == s.len());
     let easy_number_count = displays
         .iter()
-        .map(|(_, b)| b.split(' ').collect::<Vec<&str>>())
-        .flatten()
-        .filter(|s| smol_len(s))
+        .flat_map(|(_, outputs)| outputs)
+        .filter(|p| font.is_unique_length(p.count_ones()))
         .count();
     println!("1, 4, 7 or 8 appear {}", easy_number_count);
 
-    println!("Union 'abc' 'ac' = {:?}", union_pattern("abc", "ac"));
-    println!("Diff 'abc' 'ac' = {:?}", diff_pattern("abc", "ac"));
+    let sum: u64 = displays
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (patterns, outputs))| {
+            match font.solve(patterns).and_then(|w| font.decode(&w, outputs)) {
+                Ok(n) => Some(n),
+                Err(e) => {
+                    println!("Display {} could not be decoded: {:?}", i + 1, e);
+                    None
+                }
+            }
+        })
+        .sum();
+    println!("Sum of all output values: {}", sum);
 }
 
-/** Bit Id's:
- *  000
- * 1   2
- * 1   2
- *  333
- * 4   5
- * 4   5
- *  666
- */
-fn flags_to_numberdisplay(flag: u32) -> u32 {
-    match flag {
-        0b01110111 => 0,
-        0b00100100 => 1,
-        0b01011101 => 2,
-        0b01101101 => 3,
-        0b00101110 => 4,
-        0b01101011 => 5,
-        0b01111011 => 6,
-        0b00100101 => 7,
-        0b01111111 => 8,
-        0b01101111 => 9,
-        _ => 0,
-    }
-}
+// Signals and segments are both stored as bitmasks, where 'a' is bit 0, so
+// fonts can have up to 26 segments
+type Pattern = u32;
 
-fn union_pattern(l: &str, r: &str) -> Option<char> {
-    l.chars().filter(|lc| r.chars().any(|rc| rc == *lc)).next()
+// The wires or segments in a pattern, in order
+fn bits(p: Pattern) -> impl Iterator<Item = u8> {
+    (0..Pattern::BITS as u8).filter(move |&b| p & 1 << b != 0)
 }
 
-fn diff_pattern(l: &str, r: &str) -> Option<char> {
-    let a = l.chars().filter(|lc| r.chars().all(|rc| rc != *lc)).next();
-    if let None = a {
-        r.chars().filter(|rc| l.chars().all(|lc| lc != *rc)).next()
-    } else {
-        a
-    }
+fn parse_pattern(s: &str) -> Pattern {
+    s.chars()
+        .filter(|c| c.is_ascii_lowercase())
+        .fold(0, |p, c| p | 1 << (c as u8 - b'a'))
 }
 
-fn uniques(ls: &str) -> HashSet<char> {
-    let set: HashSet<char>;
-    for el in ls.chars() {
-        set.insert(el);
-    }
-    set
+fn parse_input(input: &str) -> Vec<(Vec<Pattern>, Vec<Pattern>)> {
+    input
+        .lines()
+        .filter_map(|s| {
+            let (a, b) = s.split_once(" | ")?;
+            let patterns = |s: &str| s.split_whitespace().map(parse_pattern).collect();
+            Some((patterns(a), patterns(b)))
+        })
+        .collect()
 }
 
-fn union_set(a: &HashSet<char>, b: &HashSet<char>) -> HashSet<char> {
-    a.union(&b).map(|v| *v).collect()
+#[derive(Debug, PartialEq)]
+enum DecodeError {
+    // No wiring turns the patterns into digits of the font
+    Inconsistent,
+    // More than one wiring fits, along with how many
+    Ambiguous(usize),
+    // An output pattern that isn't a digit with the found wiring
+    UnknownDigit(Pattern),
+    // A pattern using a wire the font doesn't have a segment for
+    UnknownWire(Pattern),
 }
 
-fn diff_set(a: &HashSet<char>, b: &HashSet<char>) -> HashSet<char> {
-    a.difference(&b).map(|v| *v).collect()
+// The wires are named by the same letters as the font's segments, and
+// wiring[i] = segment that the i-th of them is connected to
+type Wiring = Vec<u8>;
+
+// The segments lit for each digit, in order from 0 and up
+struct Font {
+    digits: Vec<Pattern>,
+    // Every segment used by some digit, which needn't be the first letters
+    all: Pattern,
 }
 
-/**
- * Patterns are ordered by length: 2, 3, 4, 5, 5, 5, 6, 6, 6, 7
- * This means we already know they represent the numbers: 1, 7, 4, {2, 3, 5}, {0, 6, 9}, 8
- * , meaning we only have to find 2, 3, 5 and 0, 6, 9
- */
-fn solve(patterns: &[&str]) -> [Option<char>; 10] {
-    let mut sequence: [Option<char>; 10] = [None; 10];
-    // diff(7, 1) = the first sequence
-    sequence[0] = diff_set(&uniques(patterns[1]), &uniques(patterns[0]))
-        .into_iter()
-        .next();
-    // union(diff(3, 8), 4) = second sequence
-    for p in &patterns[3..6] {
-        // if union(num, 1) == 2, we found 3
-        if union_set(&uniques(p), &uniques(patterns[0])).iter().count() == 2 {
-            let lefts = union_set(&uniques(p), &uniques(patterns[7]));
-
-            sequence[1] = diff_set(&uniques(patterns[2]), &lefts).into_iter().next();
-            if let Some(a) = sequence[1] {
-                sequence[4] = diff_set(&lefts, &HashSet::from([a])).into_iter().next();
-
-                // Union of 9 and 6 == 5, which means the last one is 0
-                
+impl Font {
+    fn new(digits: &[&str]) -> Font {
+        let digits: Vec<Pattern> = digits.iter().map(|d| parse_pattern(d)).collect();
+        let all = digits.iter().fold(0, |all, d| all | d);
+        Font { digits, all }
+    }
+
+    fn standard() -> Font {
+        Font::new(&[
+            "abcefg", "cf", "acdeg", "acdfg", "bcdf", "abdfg", "abdefg", "acf", "abcdefg", "abcdfg",
+        ])
+    }
+
+    fn is_unique_length(&self, length: u32) -> bool {
+        self.digits
+            .iter()
+            .filter(|d| d.count_ones() == length)
+            .count()
+            == 1
+    }
+
+    fn with_length(&self, length: u32) -> impl Iterator<Item = Pattern> + '_ {
+        self.digits
+            .iter()
+            .copied()
+            .filter(move |d| d.count_ones() == length)
+    }
+
+    // Which segments each wire can possibly be connected to, going by which
+    // digits have the same amount of segments as each pattern.
+    fn candidates(&self, patterns: &[Pattern]) -> Vec<Pattern> {
+        let mut candidates = vec![self.all; self.all.count_ones() as usize];
+        for &p in patterns {
+            let (any, every) = self
+                .with_length(p.count_ones())
+                .fold((0, self.all), |(any, every), d| (any | d, every & d));
+            for (wire, c) in bits(self.all).zip(candidates.iter_mut()) {
+                if p & 1 << wire != 0 {
+                    *c &= any;
+                } else {
+                    *c &= !every;
+                }
             }
         }
+
+        // A wire that can only go to one segment rules that segment out for the rest
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for wire in 0..candidates.len() {
+                let c = candidates[wire];
+                if c.count_ones() != 1 {
+                    continue;
+                }
+                for (other, o) in candidates.iter_mut().enumerate() {
+                    if other != wire && *o & c != 0 {
+                        *o &= !c;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        candidates
+    }
+
+    fn map(&self, wiring: &[u8], p: Pattern) -> Pattern {
+        bits(self.all)
+            .zip(wiring)
+            .filter(|(wire, _)| p & 1 << wire != 0)
+            .fold(0, |out, (_, segment)| out | 1 << segment)
     }
 
-    sequence
+    // The wires that have been connected, when the first n of them are
+    fn first_wires(&self, n: usize) -> Pattern {
+        bits(self.all).take(n).fold(0, |p, wire| p | 1 << wire)
+    }
+
+    // Every pattern must still be able to become a digit, given the wires
+    // that have been connected so far.
+    fn is_possible(&self, patterns: &[Pattern], wiring: &[u8]) -> bool {
+        let assigned = self.first_wires(wiring.len());
+        let assigned_segments = self.map(wiring, assigned);
+        patterns.iter().all(|&p| {
+            let image = self.map(wiring, p & assigned);
+            self.with_length(p.count_ones())
+                .any(|d| d & assigned_segments == image)
+        })
+    }
+
+    fn search(
+        &self,
+        patterns: &[Pattern],
+        candidates: &[Pattern],
+        wiring: &mut Wiring,
+        solutions: &mut Vec<Wiring>,
+    ) {
+        if wiring.len() == candidates.len() {
+            let mut digits: Vec<Pattern> = patterns.iter().map(|&p| self.map(wiring, p)).collect();
+            digits.sort();
+            digits.dedup();
+            if digits.len() == patterns.len() && digits.iter().all(|d| self.digits.contains(d)) {
+                solutions.push(wiring.clone());
+            }
+            return;
+        }
+
+        let used = self.map(wiring, self.first_wires(wiring.len()));
+        let options = candidates[wiring.len()] & !used;
+        for segment in bits(options) {
+            wiring.push(segment);
+            if self.is_possible(patterns, wiring) {
+                self.search(patterns, candidates, wiring, solutions);
+            }
+            wiring.pop();
+        }
+    }
+
+    fn check_wires(&self, patterns: &[Pattern]) -> Result<(), DecodeError> {
+        match patterns.iter().find(|&&p| p & !self.all != 0) {
+            Some(&p) => Err(DecodeError::UnknownWire(p)),
+            None => Ok(()),
+        }
+    }
+
+    fn solve(&self, patterns: &[Pattern]) -> Result<Wiring, DecodeError> {
+        self.check_wires(patterns)?;
+        let candidates = self.candidates(patterns);
+        let mut solutions = Vec::new();
+        if candidates.iter().all(|c| *c != 0) {
+            self.search(patterns, &candidates, &mut Vec::new(), &mut solutions);
+        }
+        match solutions.len() {
+            0 => Err(DecodeError::Inconsistent),
+            1 => Ok(solutions.pop().unwrap()),
+            n => Err(DecodeError::Ambiguous(n)),
+        }
+    }
+
+    fn decode(&self, wiring: &[u8], outputs: &[Pattern]) -> Result<u64, DecodeError> {
+        self.check_wires(outputs)?;
+        outputs.iter().try_fold(0, |n, &p| {
+            let segments = self.map(wiring, p);
+            match self.digits.iter().position(|d| *d == segments) {
+                Some(digit) => Ok(n * 10 + digit as u64),
+                None => Err(DecodeError::UnknownDigit(p)),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+const EXAMPLE: &str =
+    "be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe
+edbfga begcd cbg gc gcadebf fbgde acbgfd abcde gfcbed gfec | fcgedb cgb dgebacf gc
+fgaebd cg bdaec gdafb agbcfd gdcbef bgcad gfac gcb cdgabef | cg cg fdcagb cbg
+fbegcd cbd adcefb dageb afcb bc aefdc ecdab fgdeca fcdbega | efabcd cedba gadfec cb
+aecbfdg fbg gf bafeg dbefa fcge gcbea fcaegb dgceab fcbdga | gecf egdcabf bgf bfgea
+fgeab ca afcebg bdacfeg cfaedg gcfdb baec bfadeg bafgc acf | gebdcfa ecba ca fadegcb
+dbcfg fgd bdegcaf fgec aegbdf ecdfab fbedc dacgb gdcebf gf | cefg dcbef fcge gbcadfe
+bdfegc cbegaf gecbf dfcage bdacg ed bedf ced adcbefg gebcd | ed bcgafe cdgba cbgef
+egadfb cdbfeg cegd fecab cgb gbdefca cg fgcdab egfdb bfceg | gbdfcae bgc cg cgb
+gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce";
+
+#[test]
+fn test_single_display() {
+    let font = Font::standard();
+    let displays = parse_input(
+        "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf",
+    );
+    let (patterns, outputs) = &displays[0];
+    let wiring = font.solve(patterns).unwrap();
+    // a -> c, b -> f, c -> g, d -> a, e -> b, f -> d, g -> e
+    assert_eq!(wiring, vec![2, 5, 6, 0, 1, 3, 4]);
+    assert_eq!(font.decode(&wiring, outputs), Ok(5353));
+}
+
+#[test]
+fn test_example() {
+    let font = Font::standard();
+    let displays = parse_input(EXAMPLE);
+    let easy = displays
+        .iter()
+        .flat_map(|(_, outputs)| outputs)
+        .filter(|p| font.is_unique_length(p.count_ones()))
+        .count();
+    assert_eq!(easy, 26);
+
+    let outputs: Vec<u64> = displays
+        .iter()
+        .map(|(patterns, outputs)| {
+            font.decode(&font.solve(patterns).unwrap(), outputs)
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(
+        outputs,
+        vec![8394, 9781, 1197, 9361, 4873, 8418, 4548, 1625, 8717, 4315]
+    );
+    assert_eq!(outputs.iter().sum::<u64>(), 61229);
+}
+
+#[test]
+fn test_ambiguous() {
+    let font = Font::standard();
+    // Only knowing 1 and 8 leaves lots of possibilities
+    let patterns = [parse_pattern("ab"), parse_pattern("abcdefg")];
+    assert_eq!(font.solve(&patterns), Err(DecodeError::Ambiguous(2 * 120)));
+}
+
+#[test]
+fn test_corrupted() {
+    let font = Font::standard();
+    let displays = parse_input(EXAMPLE);
+    let mut patterns = displays[0].0.clone();
+    // Swap a wire in one of the five segment digits
+    patterns[5] = parse_pattern("fdceb");
+    assert_eq!(font.solve(&patterns), Err(DecodeError::Inconsistent));
+
+    let wiring = font.solve(&displays[0].0).unwrap();
+    assert_eq!(
+        font.decode(&wiring, &[parse_pattern("be"), parse_pattern("bc")]),
+        Err(DecodeError::UnknownDigit(parse_pattern("bc")))
+    );
+
+    // A letter past the last segment, both while solving and decoding
+    patterns[5] = parse_pattern("fdcez");
+    assert_eq!(
+        font.solve(&patterns),
+        Err(DecodeError::UnknownWire(parse_pattern("cdefz")))
+    );
+    assert_eq!(
+        font.decode(&wiring, &[parse_pattern("be"), parse_pattern("bh")]),
+        Err(DecodeError::UnknownWire(parse_pattern("bh")))
+    );
+}
+
+#[test]
+fn test_alternate_font() {
+    // Sevens with a hook at the top left, and nines without a bottom
+    let font = Font::new(&[
+        "abcefg", "cf", "acdeg", "acdfg", "bcdf", "abdfg", "abdefg", "abcf", "abcdefg", "abcdf",
+    ]);
+    let wiring: Wiring = vec![4, 0, 6, 2, 5, 1, 3];
+    let mut inverse = vec![0; 7];
+    for (wire, segment) in wiring.iter().enumerate() {
+        inverse[*segment as usize] = wire as u8;
+    }
+    let scrambled: Vec<Pattern> = font.digits.iter().map(|&d| font.map(&inverse, d)).collect();
+
+    assert_eq!(font.solve(&scrambled), Ok(wiring.clone()));
+    let outputs = [scrambled[7], scrambled[9], scrambled[4], scrambled[0]];
+    assert_eq!(font.decode(&wiring, &outputs), Ok(7940));
+}
+
+#[test]
+fn test_font_with_gaps() {
+    // The standard font with c renamed to h, so the segments skip c
+    let font = Font::new(&[
+        "abhefg", "hf", "ahdeg", "ahdfg", "bhdf", "abdfg", "abdefg", "ahf", "abhdefg", "abhdfg",
+    ]);
+    assert_eq!(font.solve(&font.digits), Ok(vec![0, 1, 3, 4, 5, 6, 7]));
+
+    // Wires a, b, d, e, f, g and h go to segments h, g, f, e, d, b and a
+    let wiring: Wiring = vec![7, 6, 5, 4, 3, 1, 0];
+    let scrambled: Vec<Pattern> = [
+        "hgaedb", "ad", "hafeb", "hafdb", "gafd", "hgfdb", "hgfedb", "had", "hgafedb", "hgafdb",
+    ]
+    .iter()
+    .map(|d| parse_pattern(d))
+    .collect();
+    assert_eq!(font.solve(&scrambled), Ok(wiring.clone()));
+    let outputs = [scrambled[3], scrambled[0], scrambled[8]];
+    assert_eq!(font.decode(&wiring, &outputs), Ok(308));
+    assert_eq!(
+        font.decode(&wiring, &[parse_pattern("ac")]),
+        Err(DecodeError::UnknownWire(parse_pattern("ac")))
+    );
+
+    // Letters past g work the same
+    let font = Font::new(&["x", "xy", "xyz"]);
+    let patterns: Vec<Pattern> = ["y", "yz", "xyz"]
+        .iter()
+        .map(|d| parse_pattern(d))
+        .collect();
+    assert_eq!(font.solve(&patterns), Ok(vec![25, 23, 24]));
 }
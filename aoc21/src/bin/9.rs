@@ -1,62 +1,98 @@
-use std::collections::{HashSet, VecDeque};
+use common::regions::{connected_components, Regions};
+use common::Pos;
 
-type Coord = (i32, i32);
+fn parse_input(input: &str) -> Vec<Vec<u8>> {
+    input
+        .lines()
+        .map(|s| {
+            s.chars()
+                .filter_map(|c| c.to_digit(10))
+                .map(|d| d as u8)
+                .collect()
+        })
+        .filter(|xs: &Vec<u8>| !xs.is_empty())
+        .collect()
+}
 
 fn main() {
     // const INPUT: &str = "2199943210\n3987894921\n9856789892\n8767896789\n9899965678";
     const INPUT: &str = include_str!("../inputs/9.txt");
+    let grid = parse_input(INPUT);
 
-    let grid: Vec<Vec<u8>> = INPUT.lines().map(|s|s.chars().map(|c|c.to_digit(10).unwrap().try_into().unwrap()).collect()).collect();
+    println!("Sum of the risk levels: {}", risk_level_sum(&grid));
+    println!(
+        "Product of 3 largest basins: {}",
+        largest_basins_product(&grid, 3)
+    );
+}
 
-    let mut basins: Vec<HashSet<Coord>> = Vec::new();
-    let mut sum: u32 = 0;
+fn get_2d(grid: &[Vec<u8>], (i, j): (i32, i32)) -> Option<&u8> {
+    let a: usize = j.try_into().ok()?;
+    let xs: &Vec<u8> = grid.get(a)?;
+    let b: usize = i.try_into().ok()?;
+    xs.get(b)
+}
+
+fn get_neighbours(grid: &[Vec<u8>], (i, j): (i32, i32)) -> [Option<&u8>; 4] {
+    [
+        get_2d(grid, (i - 1, j)),
+        get_2d(grid, (i + 1, j)),
+        get_2d(grid, (i, j - 1)),
+        get_2d(grid, (i, j + 1)),
+    ]
+}
+
+fn low_points(grid: &[Vec<u8>]) -> Vec<Pos> {
+    let mut points = Vec::new();
     for (j, xs) in grid.iter().enumerate() {
         for (i, val) in xs.iter().enumerate() {
-            let coords = (i.try_into().unwrap(), j.try_into().unwrap());
-            let neighbours = get_neighbours(&grid, coords);
-            if neighbours.iter().filter_map(|v|*v).all(|n|val < n) {
-                sum += u32::from(*val) + 1;
-                basins.push(HashSet::from([coords]));
-            }
-        }
-    }
-
-    println!("Sum of the risk levels: {}", sum);
-    let indices: Vec<usize> = basins.iter().enumerate().map(|(i, _)|i).collect();
-    for i in indices {
-        let mut search_queue = VecDeque::from([*basins[i].iter().next().unwrap()]);
-        while !search_queue.is_empty() {
-            if let Some(prev) = search_queue.pop_back() {
-                // Prev cannot be added without being valid. Should never panic:
-                let prev_val = get_2d(&grid, prev).unwrap();
-                for coord in neighbour_coords(prev) {
-                    if let Some(val) = get_2d(&grid, coord) {
-                        if *val != 9 && val > prev_val && !(basins.iter().any(|b|b.contains(&coord))) {
-                            search_queue.push_front(coord);
-                            basins[i].insert(coord);
-                        }
-                    }
-                }
+            let neighbours = get_neighbours(grid, (i as i32, j as i32));
+            if neighbours.iter().flatten().all(|n| val < n) {
+                points.push((i, j));
             }
         }
     }
+    points
+}
 
-    let mut sinks: Vec<usize> = basins.into_iter().map(|h|h.into_iter().count()).collect();
-    sinks.sort_unstable();
-    println!("Product of 3 largest basins: {}", (&sinks.into_iter().rev().collect::<Vec<usize>>()[..3]).iter().fold(1, |ls, l|ls*l))
+fn risk_level_sum(grid: &[Vec<u8>]) -> u32 {
+    low_points(grid)
+        .iter()
+        .map(|&(i, j)| u32::from(grid[j][i]) + 1)
+        .sum()
 }
 
-fn get_2d(grid: &Vec<Vec<u8>>, (i, j): Coord) -> Option<&u8> {
-    let a: usize = j.try_into().ok()?;
-    let xs: &Vec<u8> = grid.get(a)?;
-    let b: usize = i.try_into().ok()?;
-    xs.get(b)
+// Every location that isn't a 9 flows down into exactly one basin, so the
+// basins are simply the regions walled off by 9s.
+fn basins(grid: &[Vec<u8>]) -> Regions {
+    let width = grid.first().map_or(0, |xs| xs.len());
+    connected_components(width, grid.len(), |(i, j)| grid[j][i] != 9, |_, _| true)
 }
 
-fn neighbour_coords((i, j): Coord) -> [Coord; 4] {
-    [(i-1, j), (i+1, j), (i, j-1), (i, j+1)]
+fn largest_basins_product(grid: &[Vec<u8>], n: usize) -> usize {
+    let mut sizes: Vec<usize> = basins(grid).regions.iter().map(|r| r.size).collect();
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    sizes.iter().take(n).product()
 }
 
-fn get_neighbours(grid: &Vec<Vec<u8>>, (i, j): Coord) -> [Option<&u8>; 4] {
-    [get_2d(grid, (i-1, j)), get_2d(grid, (i+1, j)), get_2d(grid, (i, j-1)), get_2d(grid, (i, j+1))]
-}
\ No newline at end of file
+#[cfg(test)]
+const EXAMPLE: &str = "2199943210\n3987894921\n9856789892\n8767896789\n9899965678";
+
+#[test]
+fn test_example() {
+    let grid = parse_input(EXAMPLE);
+    assert_eq!(low_points(&grid), vec![(1, 0), (9, 0), (2, 2), (6, 4)]);
+    assert_eq!(risk_level_sum(&grid), 15);
+
+    let basins = basins(&grid);
+    let sizes: Vec<usize> = basins.regions.iter().map(|r| r.size).collect();
+    assert_eq!(sizes, vec![3, 9, 14, 9]);
+    // Every low point lies in its own basin
+    let mut labels: Vec<usize> = low_points(&grid)
+        .into_iter()
+        .filter_map(|p| basins.label(p))
+        .collect();
+    labels.sort();
+    assert_eq!(labels, vec![0, 1, 2, 3]);
+    assert_eq!(largest_basins_product(&grid, 3), 1134);
+}
@@ -1,109 +1,298 @@
-use itertools::Itertools;
+use std::collections::BTreeMap;
+
+type NodeId = usize;
+
+const ROOT: NodeId = 0;
 
 #[derive(PartialEq, Debug)]
-enum Object {
+enum Kind {
     File(usize),
-    Dir(Vec<Box<Object>>),
+    // Children by name, so listings come out sorted
+    Dir(BTreeMap<String, NodeId>),
 }
 
-enum Command<'a> {
-    List,
-    Navigate(&'a [&'a str]),
+#[derive(PartialEq, Debug)]
+struct Node {
+    name: String,
+    // Only the root has no parent
+    parent: Option<NodeId>,
+    kind: Kind,
 }
 
-fn parse_tree(input: &str) -> Option<Object> {
-    parse_sub_tree(&mut input.lines())
+// Every node lives in one arena and refers to others by index. Nodes are only
+// ever added after their parent, so a child always has a larger id.
+#[derive(PartialEq, Debug)]
+struct FileSystem {
+    nodes: Vec<Node>,
 }
 
-fn parse_sub_tree(input: &mut std::str::Lines) -> Option<Object> {
-    // Continue consuming lines
-    while let Some(line) = input.next() {
-        // explicitly stop on ".." - done parsing subtree
-        if line.starts_with("$ cd ..") {
-            return None;
-        } else if line.starts_with("$ cd") {
-            let mut dir = Vec::new();
-            while let Some(object) = parse_sub_tree(input) {
-                dir.push(Box::new(object));
+impl FileSystem {
+    fn new() -> FileSystem {
+        FileSystem {
+            nodes: vec![Node {
+                name: String::from("/"),
+                parent: None,
+                kind: Kind::Dir(BTreeMap::new()),
+            }],
+        }
+    }
+
+    fn children(&self, dir: NodeId) -> Option<&BTreeMap<String, NodeId>> {
+        match &self.nodes[dir].kind {
+            Kind::Dir(children) => Some(children),
+            Kind::File(_) => None,
+        }
+    }
+
+    fn child(&self, dir: NodeId, name: &str) -> Option<NodeId> {
+        self.children(dir)?.get(name).copied()
+    }
+
+    fn is_dir(&self, id: NodeId) -> bool {
+        matches!(self.nodes[id].kind, Kind::Dir(_))
+    }
+
+    // Names are a single path component, so cd can't skip levels
+    fn add(&mut self, dir: NodeId, name: &str, kind: Kind) -> Result<NodeId, String> {
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(format!("'{}' is not a valid name", name));
+        }
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            name: String::from(name),
+            parent: Some(dir),
+            kind,
+        });
+        if let Kind::Dir(children) = &mut self.nodes[dir].kind {
+            children.insert(String::from(name), id);
+        }
+        Ok(id)
+    }
+
+    // The existing directory if there already is one by that name
+    fn mkdir(&mut self, dir: NodeId, name: &str) -> Result<NodeId, String> {
+        match self.child(dir, name) {
+            Some(id) if self.is_dir(id) => Ok(id),
+            Some(_) => Err(format!("{}{} is a file", self.path(dir), name)),
+            None => self.add(dir, name, Kind::Dir(BTreeMap::new())),
+        }
+    }
+
+    // Listing the same file twice is fine, as long as it hasn't changed size
+    fn add_file(&mut self, dir: NodeId, name: &str, size: usize) -> Result<NodeId, String> {
+        match self.child(dir, name) {
+            Some(id) if self.nodes[id].kind == Kind::File(size) => Ok(id),
+            Some(_) => Err(format!("{}{} already exists", self.path(dir), name)),
+            None => self.add(dir, name, Kind::File(size)),
+        }
+    }
+
+    // Directories end with a slash
+    fn path(&self, id: NodeId) -> String {
+        let mut names = Vec::new();
+        let mut current = id;
+        while let Some(parent) = self.nodes[current].parent {
+            names.push(self.nodes[current].name.as_str());
+            current = parent;
+        }
+        let mut path = String::from("/");
+        for name in names.iter().rev() {
+            path += name;
+            path.push('/');
+        }
+        if !self.is_dir(id) {
+            path.pop();
+        }
+        path
+    }
+
+    // Total size of everything below each node, indexed by id. Going through
+    // the arena backwards sees every child before its parent.
+    fn sizes(&self) -> Vec<usize> {
+        let mut sizes = vec![0; self.nodes.len()];
+        for (id, node) in self.nodes.iter().enumerate().rev() {
+            if let Kind::File(size) = node.kind {
+                sizes[id] = size;
+            }
+            if let Some(parent) = node.parent {
+                sizes[parent] += sizes[id];
             }
-            return Some(Object::Dir(dir));
-        } else if line.chars().nth(0).map(char::is_numeric).unwrap_or(false) {
-            return Some(Object::File(line.split(' ').next()?.parse().ok()?));
         }
+        sizes
     }
-    None
-}
 
-fn parse_command<'a>(line: &'a [&'a str]) -> Option<Command<'a>> {
-    match &line[..4] {
-        ["$ cd"] => Some(Command::Navigate(&line[4..])),
-        ["$ ls"] => Some(Command::List),
-        _ => None,
+    fn directories(&self) -> impl Iterator<Item = NodeId> + '_ {
+        (0..self.nodes.len()).filter(|&id| self.is_dir(id))
+    }
+
+    // Every directory with its total size, subdirectories before the
+    // directory they are in, like du does
+    fn du(&self) -> String {
+        let sizes = self.sizes();
+        let mut output = String::new();
+        self.walk(ROOT, 0, Order::Post, &mut |id, _| {
+            if self.is_dir(id) {
+                output += &format!("{}\t{}\n", sizes[id], self.path(id));
+            }
+        });
+        output
+    }
+
+    fn tree(&self) -> String {
+        let sizes = self.sizes();
+        let mut output = String::new();
+        self.walk(ROOT, 0, Order::Pre, &mut |id, depth| {
+            let kind = if self.is_dir(id) { "dir" } else { "file" };
+            output += &format!(
+                "{}- {} ({}, size={})\n",
+                "  ".repeat(depth),
+                self.nodes[id].name,
+                kind,
+                sizes[id]
+            );
+        });
+        output
     }
+
+    // Visits everything below id (and id itself) along with how deep it is,
+    // with children in name order
+    fn walk(&self, id: NodeId, depth: usize, order: Order, visit: &mut impl FnMut(NodeId, usize)) {
+        if order == Order::Pre {
+            visit(id, depth);
+        }
+        if let Some(children) = self.children(id) {
+            for &child in children.values() {
+                self.walk(child, depth + 1, order, visit);
+            }
+        }
+        if order == Order::Post {
+            visit(id, depth);
+        }
+    }
+}
+
+// Whether a node is visited before or after the nodes below it
+#[derive(Clone, Copy, PartialEq)]
+enum Order {
+    Pre,
+    Post,
 }
 
-fn object_size(tree: &Object) -> usize {
-    match tree {
-        Object::File(s) => *s,
-        Object::Dir(d) => d.iter().map(|b| object_size(b.as_ref())).sum(),
+enum Command<'a> {
+    List,
+    Root,
+    Up,
+    Down(&'a str),
+}
+
+fn parse_command(line: &str) -> Option<Command<'_>> {
+    match line
+        .strip_prefix("$ ")?
+        .split_whitespace()
+        .collect::<Vec<&str>>()[..]
+    {
+        ["ls"] => Some(Command::List),
+        ["cd", "/"] => Some(Command::Root),
+        ["cd", ".."] => Some(Command::Up),
+        ["cd", name] => Some(Command::Down(name)),
+        _ => None,
     }
 }
 
-fn find_sum_of_max_size_directories(tree: &Object, max_size: usize) -> usize {
-    let rest = if let Object::Dir(d) = tree {
-        d.iter()
-            .map(|b| match b.as_ref() {
-                // Only count if sub-object is a directory (pretty messy. I know)
-                Object::Dir(_) => find_sum_of_max_size_directories(b.as_ref(), max_size),
-                _ => 0,
-            })
-            .sum()
-    } else {
-        0
-    };
+// Replays a terminal session. Directories can be visited any number of times
+// and in any order, and entering one that hasn't been listed yet creates it.
+fn parse_tree(input: &str) -> Result<FileSystem, String> {
+    let mut fs = FileSystem::new();
+    let mut cwd = ROOT;
+    // Whether the lines that follow are the output of ls
+    let mut listing = false;
 
-    let curr_size = object_size(tree);
-    (if curr_size <= max_size { curr_size } else { 0 }) + rest
-}
-
-fn find_smallest_directory_to_delete_for_space(tree: &Object, to_free: usize) -> Option<usize> {
-    match tree {
-        Object::Dir(d) => {
-            let rest = d
-                .iter()
-                .filter_map(|b| find_smallest_directory_to_delete_for_space(b.as_ref(), to_free))
-                .min();
-            let current_size = object_size(tree);
-            if to_free <= current_size {
-                Some(rest.unwrap_or(current_size).min(current_size))
-            } else {
-                rest
+    for (i, line) in input.lines().enumerate() {
+        let error = |e: String| format!("Line {}: {}", i + 1, e);
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with('$') {
+            listing = false;
+            match parse_command(line) {
+                Some(Command::List) => listing = true,
+                Some(Command::Root) => cwd = ROOT,
+                // Like a shell, going up from the root stays there
+                Some(Command::Up) => cwd = fs.nodes[cwd].parent.unwrap_or(ROOT),
+                Some(Command::Down(name)) => cwd = fs.mkdir(cwd, name).map_err(error)?,
+                None => return Err(error(format!("unknown command '{}'", line))),
             }
+            continue;
         }
-        _ => None,
+
+        if !listing {
+            return Err(error(format!("'{}' is not the output of ls", line)));
+        }
+        match line.split_once(' ') {
+            Some(("dir", name)) => fs.mkdir(cwd, name).map_err(error)?,
+            Some((size, name)) => match size.parse() {
+                Ok(size) => fs.add_file(cwd, name, size).map_err(error)?,
+                Err(_) => return Err(error(format!("'{}' is not a file size", size))),
+            },
+            None => return Err(error(format!("'{}' is not a file or directory", line))),
+        };
     }
+    Ok(fs)
+}
+
+fn find_sum_of_max_size_directories(fs: &FileSystem, max_size: usize) -> usize {
+    let sizes = fs.sizes();
+    fs.directories()
+        .map(|id| sizes[id])
+        .filter(|&size| size <= max_size)
+        .sum()
 }
 
-fn find_smallest_eligible_directory_to_delete(tree: &Object) -> Option<usize> {
-    let currently_free = 70000000 - object_size(tree);
-    find_smallest_directory_to_delete_for_space(tree, 30000000 - currently_free)
+fn find_smallest_directory_to_delete_for_space(fs: &FileSystem, to_free: usize) -> Option<NodeId> {
+    let sizes = fs.sizes();
+    fs.directories()
+        .filter(|&id| to_free <= sizes[id])
+        .min_by_key(|&id| sizes[id])
+}
+
+fn find_smallest_eligible_directory_to_delete(fs: &FileSystem) -> Option<NodeId> {
+    let currently_free = 70000000usize.saturating_sub(fs.sizes()[ROOT]);
+    find_smallest_directory_to_delete_for_space(fs, 30000000usize.saturating_sub(currently_free))
 }
 
 fn main() {
     const INPUT: &str = include_str!("../inputs/7.txt");
-    let tree = parse_tree(INPUT).unwrap();
+    let fs = match parse_tree(INPUT) {
+        Ok(fs) => fs,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|a| a == "--tree") {
+        print!("{}", fs.tree());
+    }
+    if args.iter().any(|a| a == "--du") {
+        print!("{}", fs.du());
+    }
+
     println!(
         "Sum of whatever: {}",
-        find_sum_of_max_size_directories(&tree, 100000)
+        find_sum_of_max_size_directories(&fs, 100000)
     );
 
-    println!("Total size used: {}", object_size(&tree));
+    let sizes = fs.sizes();
+    println!("Total size used: {}", sizes[ROOT]);
+    let dir = find_smallest_eligible_directory_to_delete(&fs).unwrap();
     println!(
-      "The smallest directory that can be deleted to achieve the space increase has a size of {}",
-      find_smallest_eligible_directory_to_delete(&tree).unwrap()
-  );
+        "The smallest directory that can be deleted to achieve the space increase is {}, with a size of {}",
+        fs.path(dir),
+        sizes[dir]
+    );
 }
 
+#[cfg(test)]
 const EXAMPLE_INPUT: &str = "$ cd /
 $ ls
 dir a
@@ -130,39 +319,132 @@ $ ls
 
 #[test]
 fn test_parse() {
-    let expected = Object::Dir(vec![
-        Box::new(Object::File(14848514)),
-        Box::new(Object::File(8504156)),
-        // a
-        Box::new(Object::Dir(vec![
-            // e
-            Box::new(Object::File(29116)),
-            Box::new(Object::File(2557)),
-            Box::new(Object::File(62596)),
-            Box::new(Object::Dir(vec![Box::new(Object::File(584))])),
-        ])),
-        // d
-        Box::new(Object::Dir(vec![
-            Box::new(Object::File(4060174)),
-            Box::new(Object::File(8033020)),
-            Box::new(Object::File(5626152)),
-            Box::new(Object::File(7214296)),
-        ])),
-    ]);
-    assert_eq!(parse_tree(EXAMPLE_INPUT).unwrap(), expected);
+    let fs = parse_tree(EXAMPLE_INPUT).unwrap();
+    assert_eq!(
+        fs.tree(),
+        "- / (dir, size=48381165)
+  - a (dir, size=94853)
+    - e (dir, size=584)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir, size=24933642)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+"
+    );
+    assert_eq!(
+        fs.du(),
+        "584\t/a/e/\n94853\t/a/\n24933642\t/d/\n48381165\t/\n"
+    );
+    let e = fs.child(fs.child(ROOT, "a").unwrap(), "e").unwrap();
+    assert_eq!(fs.path(e), "/a/e/");
+    assert_eq!(fs.path(fs.child(e, "i").unwrap()), "/a/e/i");
 }
 
 #[test]
 fn example_1() {
-    let tree = parse_tree(EXAMPLE_INPUT).unwrap();
-    assert_eq!(find_sum_of_max_size_directories(&tree, 100000), 95437);
+    let fs = parse_tree(EXAMPLE_INPUT).unwrap();
+    assert_eq!(find_sum_of_max_size_directories(&fs, 100000), 95437);
 }
 
 #[test]
 fn example_2() {
-    let tree = parse_tree(EXAMPLE_INPUT).unwrap();
+    let fs = parse_tree(EXAMPLE_INPUT).unwrap();
+    let dir = find_smallest_eligible_directory_to_delete(&fs).unwrap();
+    assert_eq!(fs.path(dir), "/d/");
+    assert_eq!(fs.sizes()[dir], 24933642);
+}
+
+#[test]
+fn test_revisits_and_jumps() {
+    // The example, but wandering around: d is entered before being listed,
+    // a is listed twice and the way back up is a jump to the root
+    let input = "$ cd d
+$ ls
+4060174 j
+8033020 d.log
+$ cd /
+$ ls
+dir a
+14848514 b.txt
+$ cd a
+$ cd e
+$ ls
+584 i
+$ cd /
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ ls
+62596 h.lst
+$ cd ..
+$ cd ..
+$ cd ..
+$ ls
+8504156 c.dat
+dir d
+$ cd d
+$ ls
+5626152 d.ext
+7214296 k";
+    let fs = parse_tree(input).unwrap();
+    assert_eq!(fs.tree(), parse_tree(EXAMPLE_INPUT).unwrap().tree());
+    assert_eq!(find_sum_of_max_size_directories(&fs, 100000), 95437);
+}
+
+#[test]
+fn test_bad_input() {
+    assert_eq!(
+        parse_tree("$ cd /\n$ rm -rf a").err(),
+        Some(String::from("Line 2: unknown command '$ rm -rf a'"))
+    );
+    assert_eq!(
+        parse_tree("$ cd /\n123 a").err(),
+        Some(String::from("Line 2: '123 a' is not the output of ls"))
+    );
     assert_eq!(
-        find_smallest_eligible_directory_to_delete(&tree).unwrap(),
-        24933642
+        parse_tree("$ ls\n12x a").err(),
+        Some(String::from("Line 2: '12x' is not a file size"))
     );
+    assert_eq!(
+        parse_tree("$ ls\n12 a\n$ cd a").err(),
+        Some(String::from("Line 3: /a is a file"))
+    );
+    assert_eq!(
+        parse_tree("$ ls\n12 a\n$ ls\n13 a").err(),
+        Some(String::from("Line 4: /a already exists"))
+    );
+    assert_eq!(
+        parse_tree("$ cd /a/b").err(),
+        Some(String::from("Line 1: '/a/b' is not a valid name"))
+    );
+    assert_eq!(
+        parse_tree("$ cd a/b").err(),
+        Some(String::from("Line 1: 'a/b' is not a valid name"))
+    );
+    assert_eq!(
+        parse_tree("$ ls\ndir ..").err(),
+        Some(String::from("Line 2: '..' is not a valid name"))
+    );
+    assert_eq!(
+        parse_tree("$ ls\n12 a/b").err(),
+        Some(String::from("Line 2: 'a/b' is not a valid name"))
+    );
+}
+
+#[test]
+fn test_full_disk() {
+    // More in use than the disk holds, so everything needs freeing
+    let fs = parse_tree("$ ls\ndir a\n1 c\n$ cd a\n$ ls\n80000000 b").unwrap();
+    let dir = find_smallest_eligible_directory_to_delete(&fs).unwrap();
+    assert_eq!(fs.path(dir), "/a/");
 }
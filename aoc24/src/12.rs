@@ -1,3 +1,4 @@
+use common::regions::connected_components;
 use std::collections::{BTreeSet, HashMap, HashSet};
 use std::time::Instant;
 
@@ -13,58 +14,26 @@ fn get_neighbours(pos: Pos) -> [Pos; 4] {
     ]
 }
 
-fn point_intersects_with_cluster(point: Pos, cluster: &HashSet<Pos>) -> bool {
-    for neighbour in get_neighbours(point) {
-        if cluster.contains(&neighbour) {
-            return true;
-        }
-    }
-
-    false
-}
-
-fn cluster(mut points_to_process: Vec<Pos>) -> Vec<HashSet<Pos>> {
-    // Logic:
-    //  1. First pop a point in the processing list to become the current cluster
-    //  2. Then, iterate through the list attempting to find any intersection.
-    //    a. If a cluster intersection was found, add that point to the current set. Start over from step 2.
-    //    b. Otherwise, start over from step 1.
-    //  3. When the processing list is done, clusters are done.
-    let mut clusters = Vec::new();
-
-    while let Some(core_point) = points_to_process.pop() {
-        let mut current_cluster = HashSet::from([core_point]);
-        while let Some(index) = points_to_process
-            .iter()
-            .position(|point| point_intersects_with_cluster(*point, &current_cluster))
-        {
-            current_cluster.insert(points_to_process.remove(index));
-        }
-        clusters.push(current_cluster);
-    }
-
-    clusters
-}
-
 fn collect_connected_sets(input: &str) -> Vec<HashSet<Pos>> {
-    let mut grouped_sets: HashMap<u8, Vec<Pos>> = HashMap::new();
-
-    for (y, line) in input.trim().lines().enumerate() {
-        for (x, c) in line.as_bytes().iter().enumerate() {
-            if !grouped_sets.contains_key(c) {
-                grouped_sets.insert(*c, vec![(x as i64, y as i64)]);
-            } else {
-                grouped_sets.get_mut(c).unwrap().push((x as i64, y as i64));
-            }
-        }
-    }
-
-    // Now split the labeled groups into clusters
-    grouped_sets
-        .into_iter()
-        .map(|(_, group)| cluster(group).into_iter())
-        .flatten()
-        .collect()
+    let grid: Vec<&[u8]> = input.trim().lines().map(|line| line.as_bytes()).collect();
+    let width = grid.first().map_or(0, |line| line.len());
+
+    // Plots of the same plant that are next to each other are part of the same region
+    connected_components(
+        width,
+        grid.len(),
+        |(x, y)| x < grid[y].len(),
+        |(x0, y0), (x1, y1)| grid[y0][x0] == grid[y1][x1],
+    )
+    .cells()
+    .into_iter()
+    .map(|cells| {
+        cells
+            .into_iter()
+            .map(|(x, y)| (x as i64, y as i64))
+            .collect()
+    })
+    .collect()
 }
 
 fn get_cluster_perimeter(cluster: &HashSet<Pos>) -> usize {
@@ -0,0 +1,226 @@
+// Cellular automata on dense 2D grids. A Rule says how a cell changes based
+// on its neighbours, possibly over several phases per step, and the Automaton
+// applies it to every cell at once.
+use crate::Pos;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edges {
+    // Cells past the edge don't exist
+    Bounded,
+    // Leaving one side enters from the opposite one
+    Wrap,
+}
+
+// Offsets of the cells that count as neighbours
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Neighbourhood(pub Vec<(i64, i64)>);
+
+impl Neighbourhood {
+    pub fn moore() -> Neighbourhood {
+        Neighbourhood(
+            (-1..=1)
+                .flat_map(|y| (-1..=1).map(move |x| (x, y)))
+                .filter(|&d| d != (0, 0))
+                .collect(),
+        )
+    }
+
+    pub fn von_neumann() -> Neighbourhood {
+        Neighbourhood(vec![(0, -1), (-1, 0), (1, 0), (0, 1)])
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid<T> {
+    pub width: usize,
+    pub height: usize,
+    pub edges: Edges,
+    pub neighbourhood: Neighbourhood,
+    // Row major
+    pub cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    pub fn new(width: usize, height: usize, cells: Vec<T>) -> Grid<T> {
+        assert_eq!(width * height, cells.len());
+        Grid {
+            width,
+            height,
+            edges: Edges::Bounded,
+            neighbourhood: Neighbourhood::moore(),
+            cells,
+        }
+    }
+
+    pub fn parse(input: &str, f: impl Fn(char) -> T) -> Grid<T> {
+        let rows: Vec<&str> = input
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .collect();
+        let width = rows.first().map_or(0, |r| r.chars().count());
+        let cells: Vec<T> = rows.iter().flat_map(|r| r.chars()).map(f).collect();
+        Grid::new(width, rows.len(), cells)
+    }
+
+    pub fn with_edges(mut self, edges: Edges) -> Grid<T> {
+        self.edges = edges;
+        self
+    }
+
+    pub fn with_neighbourhood(mut self, neighbourhood: Neighbourhood) -> Grid<T> {
+        self.neighbourhood = neighbourhood;
+        self
+    }
+
+    pub fn index(&self, (x, y): Pos) -> usize {
+        y * self.width + x
+    }
+
+    pub fn pos(&self, i: usize) -> Pos {
+        (i % self.width, i / self.width)
+    }
+
+    pub fn get(&self, pos: Pos) -> &T {
+        &self.cells[self.index(pos)]
+    }
+
+    // The position d away from pos, if there is one
+    pub fn offset(&self, (x, y): Pos, (dx, dy): (i64, i64)) -> Option<Pos> {
+        let (x, y) = (x as i64 + dx, y as i64 + dy);
+        let (w, h) = (self.width as i64, self.height as i64);
+        match self.edges {
+            Edges::Bounded if 0 <= x && x < w && 0 <= y && y < h => Some((x as usize, y as usize)),
+            Edges::Bounded => None,
+            Edges::Wrap => Some((x.rem_euclid(w) as usize, y.rem_euclid(h) as usize)),
+        }
+    }
+
+    pub fn relative(&self, pos: Pos, d: (i64, i64)) -> Option<&T> {
+        self.offset(pos, d).map(|p| self.get(p))
+    }
+
+    pub fn neighbours(&self, pos: Pos) -> impl Iterator<Item = Pos> + '_ {
+        self.neighbourhood
+            .0
+            .iter()
+            .filter_map(move |&d| self.offset(pos, d))
+    }
+
+    pub fn render(&self, f: impl Fn(&T) -> char) -> String {
+        self.cells
+            .chunks(self.width.max(1))
+            .map(|row| row.iter().map(&f).collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+// A step runs every phase, each computing all cells from the result of the
+// previous one, followed by a cascade: cells that fire spread to their
+// neighbours, which may in turn fire, and every fired cell is settled once
+// nothing more fires. Each cell fires at most once per step.
+pub trait Rule {
+    type Cell: Clone + PartialEq;
+
+    fn phases(&self) -> usize {
+        1
+    }
+
+    fn update(&self, phase: usize, grid: &Grid<Self::Cell>, pos: Pos) -> Self::Cell;
+
+    fn fires(&self, _cell: &Self::Cell) -> bool {
+        false
+    }
+
+    fn spread(&self, _neighbour: &mut Self::Cell) {}
+
+    fn settle(&self, _cell: &mut Self::Cell) {}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepStats {
+    // Counting from 1
+    pub step: usize,
+    // Cells changed by the phases, summed over all of them
+    pub changed: usize,
+    pub fired: usize,
+}
+
+pub struct Automaton<R: Rule> {
+    pub rule: R,
+    pub grid: Grid<R::Cell>,
+    pub steps: usize,
+    // The buffer the next phase is written to
+    back: Vec<R::Cell>,
+}
+
+impl<R: Rule> Automaton<R> {
+    pub fn new(rule: R, grid: Grid<R::Cell>) -> Automaton<R> {
+        let back = grid.cells.clone();
+        Automaton {
+            rule,
+            grid,
+            steps: 0,
+            back,
+        }
+    }
+
+    pub fn step(&mut self) -> StepStats {
+        let mut changed = 0;
+        for phase in 0..self.rule.phases() {
+            for (i, next) in self.back.iter_mut().enumerate() {
+                *next = self.rule.update(phase, &self.grid, self.grid.pos(i));
+                if *next != self.grid.cells[i] {
+                    changed += 1;
+                }
+            }
+            std::mem::swap(&mut self.grid.cells, &mut self.back);
+        }
+
+        let mut fired = vec![false; self.grid.cells.len()];
+        let mut queue: Vec<usize> = Vec::new();
+        for (i, cell) in self.grid.cells.iter().enumerate() {
+            if self.rule.fires(cell) {
+                fired[i] = true;
+                queue.push(i);
+            }
+        }
+        let mut fired_count = 0;
+        while let Some(i) = queue.pop() {
+            fired_count += 1;
+            let neighbours: Vec<Pos> = self.grid.neighbours(self.grid.pos(i)).collect();
+            for n in neighbours {
+                let n = self.grid.index(n);
+                self.rule.spread(&mut self.grid.cells[n]);
+                if !fired[n] && self.rule.fires(&self.grid.cells[n]) {
+                    fired[n] = true;
+                    queue.push(n);
+                }
+            }
+        }
+        for (cell, _) in self.grid.cells.iter_mut().zip(&fired).filter(|(_, f)| **f) {
+            self.rule.settle(cell);
+        }
+
+        self.steps += 1;
+        StepStats {
+            step: self.steps,
+            changed,
+            fired: fired_count,
+        }
+    }
+
+    pub fn run(&mut self, steps: usize) -> Vec<StepStats> {
+        (0..steps).map(|_| self.step()).collect()
+    }
+
+    // Steps until a step matches, giving up after the limit
+    pub fn run_until(
+        &mut self,
+        limit: usize,
+        done: impl Fn(&StepStats) -> bool,
+    ) -> Option<StepStats> {
+        (0..limit).map(|_| self.step()).find(done)
+    }
+}
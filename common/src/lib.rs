@@ -0,0 +1,6 @@
+pub mod automaton;
+pub mod pbm;
+pub mod regions;
+
+// A cell of a grid, as (x, y)
+pub type Pos = (usize, usize);
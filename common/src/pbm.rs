@@ -0,0 +1,34 @@
+// Plain PBM images, for looking at grids with an image viewer
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::Pos;
+
+// 1 is black, so set cells are drawn black
+pub fn to_pbm(width: usize, height: usize, is_set: impl Fn(Pos) -> bool) -> String {
+    let mut out = format!("P1\n{} {}\n", width, height);
+    for y in 0..height {
+        let row = (0..width)
+            .map(|x| if is_set((x, y)) { "1" } else { "0" })
+            .collect::<Vec<&str>>();
+        out += &row.join(" ");
+        out.push('\n');
+    }
+    out
+}
+
+// Writes each image to the directory as <name>-00.pbm, <name>-01.pbm, ...
+pub fn export(dir: &Path, name: &str, images: impl IntoIterator<Item = String>) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for (step, image) in images.into_iter().enumerate() {
+        fs::write(dir.join(format!("{}-{:02}.pbm", name, step)), image)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_to_pbm() {
+    assert_eq!(to_pbm(2, 2, |(x, y)| x == y), "P1\n2 2\n1 0\n0 1\n");
+    assert_eq!(to_pbm(3, 1, |(x, _)| x == 2), "P1\n3 1\n0 0 1\n");
+}
@@ -0,0 +1,250 @@
+// Splitting a grid into connected regions, for flood fills like smoke
+// basins or garden plots. The union-find underneath is usable on its own.
+use crate::Pos;
+
+// Disjoint-set forest, with union by size and path halving
+pub struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    pub fn new(n: usize) -> UnionFind {
+        UnionFind {
+            parent: (0..n).collect(),
+            size: vec![1; n],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_empty()
+    }
+
+    pub fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    // Returns false if a and b already were in the same set
+    pub fn union(&mut self, a: usize, b: usize) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        true
+    }
+
+    pub fn same_set(&mut self, a: usize, b: usize) -> bool {
+        self.find(a) == self.find(b)
+    }
+
+    pub fn set_size(&mut self, x: usize) -> usize {
+        let root = self.find(x);
+        self.size[root]
+    }
+}
+
+// Inclusive on both ends
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub min: Pos,
+    pub max: Pos,
+}
+
+impl BoundingBox {
+    fn extend(&mut self, (x, y): Pos) {
+        self.min = (self.min.0.min(x), self.min.1.min(y));
+        self.max = (self.max.0.max(x), self.max.1.max(y));
+    }
+
+    pub fn width(&self) -> usize {
+        self.max.0 - self.min.0 + 1
+    }
+
+    pub fn height(&self) -> usize {
+        self.max.1 - self.min.1 + 1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub size: usize,
+    pub bounds: BoundingBox,
+}
+
+pub struct Regions {
+    pub width: usize,
+    pub height: usize,
+    // Region id of every cell in row major order, or None for excluded cells
+    pub labels: Vec<Option<usize>>,
+    pub regions: Vec<Region>,
+}
+
+impl Regions {
+    pub fn label(&self, (x, y): Pos) -> Option<usize> {
+        if x < self.width && y < self.height {
+            self.labels[y * self.width + x]
+        } else {
+            None
+        }
+    }
+
+    // The cells of every region, indexed by region id
+    pub fn cells(&self) -> Vec<Vec<Pos>> {
+        let mut cells: Vec<Vec<Pos>> = self
+            .regions
+            .iter()
+            .map(|r| Vec::with_capacity(r.size))
+            .collect();
+        for (i, label) in self.labels.iter().enumerate() {
+            if let Some(id) = label {
+                cells[*id].push((i % self.width, i / self.width));
+            }
+        }
+        cells
+    }
+}
+
+// Labels the regions of a width x height grid, where cells that are included
+// belong to the same region as the orthogonal neighbours they're connected
+// to. Regions are numbered in the order their first cell appears row by row.
+pub fn connected_components(
+    width: usize,
+    height: usize,
+    include: impl Fn(Pos) -> bool,
+    connected: impl Fn(Pos, Pos) -> bool,
+) -> Regions {
+    let included: Vec<bool> = (0..width * height)
+        .map(|i| include((i % width, i / width)))
+        .collect();
+
+    // Joining with the right and lower neighbour covers every edge once
+    let mut sets = UnionFind::new(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            if !included[i] {
+                continue;
+            }
+            if x + 1 < width && included[i + 1] && connected((x, y), (x + 1, y)) {
+                sets.union(i, i + 1);
+            }
+            if y + 1 < height && included[i + width] && connected((x, y), (x, y + 1)) {
+                sets.union(i, i + width);
+            }
+        }
+    }
+
+    let mut ids: Vec<Option<usize>> = vec![None; width * height];
+    let mut labels = vec![None; width * height];
+    let mut regions: Vec<Region> = Vec::new();
+    for i in 0..width * height {
+        if !included[i] {
+            continue;
+        }
+        let cell = (i % width, i / width);
+        let root = sets.find(i);
+        let id = *ids[root].get_or_insert_with(|| {
+            regions.push(Region {
+                size: 0,
+                bounds: BoundingBox {
+                    min: cell,
+                    max: cell,
+                },
+            });
+            regions.len() - 1
+        });
+        regions[id].size += 1;
+        regions[id].bounds.extend(cell);
+        labels[i] = Some(id);
+    }
+
+    Regions {
+        width,
+        height,
+        labels,
+        regions,
+    }
+}
+
+#[test]
+fn test_union_find() {
+    let mut sets = UnionFind::new(6);
+    assert!(sets.union(0, 1));
+    assert!(sets.union(2, 3));
+    assert!(sets.union(1, 3));
+    assert!(!sets.union(0, 2));
+    assert!(sets.same_set(0, 3));
+    assert!(!sets.same_set(0, 4));
+    assert_eq!(sets.set_size(2), 4);
+    assert_eq!(sets.set_size(5), 1);
+}
+
+#[test]
+fn test_connected_components() {
+    let grid: Vec<&[u8]> = vec![b"aab.", b"a.bb", b"..ab"];
+    let regions = connected_components(
+        4,
+        3,
+        |(x, y)| grid[y][x] != b'.',
+        |(x0, y0), (x1, y1)| grid[y0][x0] == grid[y1][x1],
+    );
+    assert_eq!(
+        regions.labels,
+        vec![
+            Some(0),
+            Some(0),
+            Some(1),
+            None,
+            Some(0),
+            None,
+            Some(1),
+            Some(1),
+            None,
+            None,
+            Some(2),
+            Some(1)
+        ]
+    );
+    assert_eq!(
+        regions.regions,
+        vec![
+            Region {
+                size: 3,
+                bounds: BoundingBox {
+                    min: (0, 0),
+                    max: (1, 1)
+                }
+            },
+            Region {
+                size: 4,
+                bounds: BoundingBox {
+                    min: (2, 0),
+                    max: (3, 2)
+                }
+            },
+            Region {
+                size: 1,
+                bounds: BoundingBox {
+                    min: (2, 2),
+                    max: (2, 2)
+                }
+            },
+        ]
+    );
+    assert_eq!(regions.label((3, 0)), None);
+    assert_eq!(regions.label((4, 0)), None);
+    assert_eq!(regions.cells()[2], vec![(2, 2)]);
+}